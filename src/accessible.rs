@@ -0,0 +1,140 @@
+use crate::internal::*;
+use crate::i18n::{Lang, Msg};
+
+/// 近い順に読み上げるrobotの数
+const NEAREST_THREATS: usize = 3;
+
+/// 方向を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+  /// 北(上)
+  North,
+  /// 北東
+  NorthEast,
+  /// 東(右)
+  East,
+  /// 南東
+  SouthEast,
+  /// 南(下)
+  South,
+  /// 南西
+  SouthWest,
+  /// 西(左)
+  West,
+  /// 北西
+  NorthWest,
+  /// その場にとどまる
+  Stay,
+  /// 同じ座標
+  Here,
+}
+
+/// 周囲のマスに移動したときの状態を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NeighbourState {
+  /// 次のターンを生き残れる
+  Safe,
+  /// 次のターンにrobotに倒される可能性がある
+  Unsafe,
+  /// fieldの外
+  Wall,
+  /// robotやscrapがあって移動できない
+  Blocked,
+}
+
+/// 周囲のマスの方向(その場を含む)
+const NEIGHBOURS: [(i64, i64, Direction); 9] = [
+  (0, -1, Direction::North), (1, -1, Direction::NorthEast), (1, 0, Direction::East),
+  (1, 1, Direction::SouthEast), (0, 1, Direction::South), (-1, 1, Direction::SouthWest),
+  (-1, 0, Direction::West), (-1, -1, Direction::NorthWest), (0, 0, Direction::Stay),
+];
+
+/// `from`から見た`to`の方向を返します
+/// 同じ座標の場合は`Direction::Here`を返します
+/// * `from` - 基準の座標
+/// * `to` - 方向を調べる座標
+pub fn direction(from: Point, to: Point) -> Direction {
+  let dx = (to.x as i64 - from.x as i64).signum();
+  let dy = (to.y as i64 - from.y as i64).signum();
+  NEIGHBOURS.iter()
+    .find(|(x, y, _)| *x == dx && *y == dy)
+    .map(|(_, _, direction)| if *direction == Direction::Stay { Direction::Here } else { *direction })
+    .unwrap_or(Direction::Here)
+}
+
+/// 指定の方向に移動して次のターンを生き残れるかを判定します
+/// fieldの外には`Wall`を、robotやscrapがあって移動できないマスには`Blocked`を返します
+/// robotの隣のマスとsuper_robotから2マス以内のマスは、robotどうしの衝突で助かる場合も`Unsafe`とします
+/// * `field` - 現在のfield
+/// * `who` - playerの番号
+/// * `pos` - 移動先の座標
+fn neighbour_state(field: &Field, who: usize, pos: Option<Point>) -> NeighbourState {
+  let pos = match pos {
+    Some(pos) if pos.x < field.width && pos.y < field.height => pos,
+    _ => return NeighbourState::Wall,
+  };
+  if !field.can_move_player(who, pos) {
+    return NeighbourState::Blocked;
+  }
+  if field.is_threatened(pos) { NeighbourState::Unsafe } else { NeighbourState::Safe }
+}
+
+/// 画面を見なくても遊べるように、指定のplayerから見たfieldの状況を文章で返します
+/// 1行目はplayerの座標と残りの数、2行目は近いrobot、3行目は周囲のマスが安全かです
+/// 座標は左上を(1, 1)として数えます
+/// * `field` - 説明するfield
+/// * `who` - playerの番号(0: 1人目, 1: 2人目)
+/// * `lang` - 表示する言語
+pub fn summary(field: &Field, who: usize, lang: Lang) -> Vec<String> {
+  let pos = match field.player(who) {
+    Some(pos) => pos,
+    None => return vec![Msg::SummaryDown(who).text(lang)],
+  };
+  let mut lines = vec![Msg::SummaryPosition(who, pos.x + 1, pos.y + 1,
+    field.robots_pos.len() + field.smart_robots_pos.len(), field.super_robots_pos.len(), field.scraps_pos.len()).text(lang)];
+
+  let mut threats: Vec<(usize, bool, Point)> = field.robots_pos.iter().chain(field.smart_robots_pos.iter())
+    .map(|p| (distance(pos, *p), false, *p))
+    .chain(field.super_robots_pos.iter().map(|p| (distance(pos, *p), true, *p)))
+    .collect();
+  threats.sort_by_key(|(d, _, p)| (*d, p.y, p.x));
+  let threats: Vec<String> = threats.iter().take(NEAREST_THREATS)
+    .map(|(d, super_robot, p)| Msg::Threat(*super_robot, *d, direction(pos, *p)).text(lang))
+    .collect();
+  if threats.is_empty() {
+    lines.push(Msg::NoRobotsLeft.text(lang));
+  } else {
+    lines.push(Msg::Nearest(&threats.join(", ")).text(lang));
+  }
+
+  let neighbours: Vec<String> = NEIGHBOURS.iter().map(|(dx, dy, direction)| {
+    let x = pos.x as i64 + dx;
+    let y = pos.y as i64 + dy;
+    let target = if x < 0 || y < 0 { None } else { Some(Point::new(x as usize, y as usize)) };
+    Msg::Neighbour(*direction, neighbour_state(field, who, target)).text(lang)
+  }).collect();
+  lines.push(neighbours.join(", "));
+  lines
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn summary_test() {
+    let field = Field::from_rows(&["+...", "..@.", "....", "...*"], Point::new(0, 0)).unwrap();
+    let lines = summary(&field, 0, Lang::En);
+    assert_eq!(lines[0], "player 1 at column 3, row 2. 1 robots, 0 super robots, 1 scraps");
+    assert_eq!(lines[1], "nearest: robot 2 north-west");
+    // robotが次に動くマスに移動すると捕まる
+    assert_eq!(lines[2], "north safe, north-east safe, east safe, south-east safe, \
+      south safe, south-west safe, west unsafe, north-west unsafe, stay safe");
+
+    let field = Field::from_rows(&["@*"], Point::new(0, 0)).unwrap();
+    assert_eq!(summary(&field, 0, Lang::En)[2], "north wall, north-east wall, east blocked, south-east wall, \
+      south wall, south-west wall, west wall, north-west wall, stay safe");
+    assert_eq!(direction(Point::new(3, 3), Point::new(3, 3)), Direction::Here);
+    assert_eq!(direction(Point::new(3, 3), Point::new(9, 1)), Direction::NorthEast);
+  }
+}
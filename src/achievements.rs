@@ -0,0 +1,202 @@
+use std::fs;
+use crate::internal::TurnReport;
+
+/// 実績の判定に使うゲーム中の出来事を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+  /// levelを開始した
+  LevelStarted(u32),
+  /// robotが移動した
+  Turn {
+    /// robotの移動で起きたこと
+    report: TurnReport,
+    /// 止まるモードか
+    stop: bool,
+  },
+  /// levelをクリアした
+  LevelCleared {
+    /// クリアしたlevel
+    level: u32,
+    /// levelの中でランダム移動を使ったか
+    teleported: bool,
+    /// 止まるモードでクリアしたか
+    stop: bool,
+  },
+}
+
+/// 実績の種類を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Achievement {
+  /// 初めてrobotを倒した
+  FirstScrap,
+  /// super_robotを倒した
+  SuperScrap,
+  /// ランダム移動を使わずにlevelをクリアした
+  NoTeleport,
+  /// 1ターンで10体のrobotを倒した
+  TenInOneTurn,
+  /// 止まるモードでlevelをクリアした
+  StopWin,
+  /// level 10に到達した
+  Level10,
+}
+
+impl Achievement {
+  /// 全ての実績
+  pub const ALL: [Achievement; 6] = [
+    Achievement::FirstScrap,
+    Achievement::SuperScrap,
+    Achievement::NoTeleport,
+    Achievement::TenInOneTurn,
+    Achievement::StopWin,
+    Achievement::Level10,
+  ];
+
+  /// 保存に使う識別子を返します
+  pub fn id(&self) -> &'static str {
+    match self {
+      Achievement::FirstScrap => "first_scrap",
+      Achievement::SuperScrap => "super_scrap",
+      Achievement::NoTeleport => "no_teleport",
+      Achievement::TenInOneTurn => "ten_in_one_turn",
+      Achievement::StopWin => "stop_win",
+      Achievement::Level10 => "level_10",
+    }
+  }
+
+  /// 実績の名前を返します
+  pub fn title(&self) -> &'static str {
+    match self {
+      Achievement::FirstScrap => "First Scrap",
+      Achievement::SuperScrap => "Bigger They Are",
+      Achievement::NoTeleport => "Grounded",
+      Achievement::TenInOneTurn => "Scrap Yard",
+      Achievement::StopWin => "Stand Your Ground",
+      Achievement::Level10 => "Veteran",
+    }
+  }
+
+  /// 実績の条件を返します
+  pub fn description(&self) -> &'static str {
+    match self {
+      Achievement::FirstScrap => "destroy a robot",
+      Achievement::SuperScrap => "destroy a super robot",
+      Achievement::NoTeleport => "clear a level without teleporting",
+      Achievement::TenInOneTurn => "destroy 10 robots in one turn",
+      Achievement::StopWin => "clear a level in stop mode",
+      Achievement::Level10 => "reach level 10",
+    }
+  }
+
+  /// 出来事が実績の条件を満たすかを判定します
+  /// * `event` - ゲーム中の出来事
+  fn unlocked_by(&self, event: &Event) -> bool {
+    match (self, event) {
+      (Achievement::FirstScrap, Event::Turn { report, .. }) => report.robots + report.super_robots > 0,
+      (Achievement::SuperScrap, Event::Turn { report, .. }) => report.super_robots > 0,
+      (Achievement::TenInOneTurn, Event::Turn { report, .. }) => report.robots + report.super_robots >= 10,
+      (Achievement::NoTeleport, Event::LevelCleared { teleported, .. }) => !teleported,
+      (Achievement::StopWin, Event::LevelCleared { stop, .. }) => *stop,
+      (Achievement::Level10, Event::LevelStarted(level)) => *level >= 10,
+      _ => false,
+    }
+  }
+}
+
+/// プロフィールごとに獲得した実績を表します
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Achievements {
+  /// プロフィール名
+  pub profile: String,
+  /// 獲得した実績
+  pub unlocked: Vec<Achievement>,
+}
+
+impl Achievements {
+  /// 実績ファイルから指定のプロフィールの実績を読み込みます
+  /// ファイルが無い場合は実績の無い状態を返します
+  /// * `filename` - 実績ファイル名
+  /// * `profile` - プロフィール名
+  pub fn load(filename: &str, profile: &str) -> Achievements {
+    let file = fs::read_to_string(filename).unwrap_or_default();
+    let unlocked = Achievement::ALL.iter().copied().filter(|a| {
+      file.lines().any(|line| line.split_once(' ') == Some((profile, a.id())))
+    }).collect();
+    Achievements { profile: profile.to_string(), unlocked }
+  }
+
+  /// 実績ファイルの指定のプロフィールの実績を書き換えます
+  /// 他のプロフィールの実績はそのまま残します
+  /// * `filename` - 実績ファイル名
+  pub fn save(&self, filename: &str) -> std::io::Result<()> {
+    let file = fs::read_to_string(filename).unwrap_or_default();
+    let mut lines: Vec<String> = file.lines()
+      .filter(|line| line.split_once(' ').map(|(p, _)| p) != Some(self.profile.as_str()))
+      .map(|line| line.to_string())
+      .collect();
+    for a in &self.unlocked {
+      lines.push(format!("{} {}", self.profile, a.id()));
+    }
+    fs::write(filename, lines.join("\n") + "\n")
+  }
+
+  /// 実績を獲得済みかを判定します
+  /// * `achievement` - 判定する実績
+  pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+    self.unlocked.contains(&achievement)
+  }
+
+  /// 出来事から実績を判定し、新しく獲得した実績を返します
+  /// * `event` - ゲーム中の出来事
+  pub fn notify(&mut self, event: &Event) -> Vec<Achievement> {
+    let new: Vec<Achievement> = Achievement::ALL.iter().copied()
+      .filter(|a| !self.is_unlocked(*a) && a.unlocked_by(event))
+      .collect();
+    self.unlocked.extend(new.iter().copied());
+    new
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn notify_test() {
+    let mut achievements = Achievements { profile: "test".to_string(), unlocked: Vec::new() };
+    assert!(achievements.notify(&Event::LevelStarted(9)).is_empty());
+    assert_eq!(achievements.notify(&Event::LevelStarted(10)), vec![Achievement::Level10]);
+    assert!(achievements.notify(&Event::LevelStarted(11)).is_empty());
+
+    let report = TurnReport { robots: 9, super_robots: 1, killed_by: None };
+    assert_eq!(achievements.notify(&Event::Turn { report, stop: false }),
+      vec![Achievement::FirstScrap, Achievement::SuperScrap, Achievement::TenInOneTurn]);
+
+    let cleared = Event::LevelCleared { level: 1, teleported: true, stop: true };
+    assert_eq!(achievements.notify(&cleared), vec![Achievement::StopWin]);
+    let cleared = Event::LevelCleared { level: 2, teleported: false, stop: false };
+    assert_eq!(achievements.notify(&cleared), vec![Achievement::NoTeleport]);
+    assert_eq!(achievements.unlocked.len(), Achievement::ALL.len());
+  }
+
+#[test]
+  fn profile_file_test() {
+    let path = std::env::temp_dir().join(format!("robots-achievements-{}.dat", std::process::id()));
+    let filename = path.to_str().unwrap();
+    let _ = fs::remove_file(filename);
+
+    let mut alice = Achievements::load(filename, "alice");
+    assert!(alice.unlocked.is_empty());
+    alice.notify(&Event::LevelStarted(10));
+    alice.save(filename).unwrap();
+
+    let mut bob = Achievements::load(filename, "bob");
+    assert!(bob.unlocked.is_empty());
+    bob.notify(&Event::LevelCleared { level: 1, teleported: false, stop: true });
+    bob.save(filename).unwrap();
+
+    assert_eq!(Achievements::load(filename, "alice").unlocked, vec![Achievement::Level10]);
+    assert_eq!(Achievements::load(filename, "bob").unlocked, vec![Achievement::NoTeleport, Achievement::StopWin]);
+    fs::remove_file(filename).unwrap();
+  }
+}
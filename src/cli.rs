@@ -0,0 +1,333 @@
+use std::str::FromStr;
+use crate::config::{Config, REALTIME_TICK_MS};
+use crate::config;
+use crate::daily;
+use crate::difficulty::Difficulty;
+use crate::i18n::Lang;
+use crate::internal::Field;
+use crate::net;
+use crate::spectate;
+use crate::theme::Theme;
+
+/// fieldの横幅と縦幅の上限
+const MAX_FIELD_SIZE: u64 = config::MAX_FIELD_SIZE as u64;
+/// デイリーモードでは指定できない、fieldとrobotに関わるオプション
+const DAILY_FIXED_OPTIONS: [&str; 10] = ["--width", "--height", "--difficulty", "--robots", "--max-robots",
+  "--min-distance", "--smart-robots", "--start-level", "--seed", "--realtime"];
+/// 最初のlevelの上限
+const MAX_START_LEVEL: u64 = 10000;
+/// リアルタイムモードでrobotが動く間隔の上限(ミリ秒)
+const MAX_TICK_MS: u64 = 60000;
+
+/// サブコマンドを表します
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+  /// 累計の記録を表示する
+  Stats,
+  /// 実績の一覧を表示する
+  Achievements,
+  /// その日のデイリーモードのランキングを表示する
+  Daily,
+  /// 指定のポートで対戦相手を待つ
+  Host(u16),
+  /// 指定のアドレスのゲームを観戦する
+  Watch(String),
+  /// 指定の番号(1始まり)のパズルからパズルモードを始める
+  Puzzle(usize),
+  /// 協力プレイを始める
+  Coop,
+  /// 指定のアドレスの対戦相手に接続する
+  Join(String),
+}
+
+/// コマンドライン引数を解析した結果を表します
+#[derive(Clone, Debug)]
+pub struct Cli {
+  /// サブコマンド
+  pub command: Option<Command>,
+  /// ゲームの設定
+  pub config: Config,
+  /// ncursesを使わずに描画するか
+  pub ansi: bool,
+  /// 確認せずに中断したゲームを再開するか
+  pub resume: bool,
+  /// 観戦者に配信するアドレス
+  pub spectate: Option<String>,
+  /// 使い方を表示するか
+  pub help: bool,
+}
+
+/// コマンドライン引数が正しくない理由を表します
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CliError {
+  /// 知らないオプション
+  UnknownOption(String),
+  /// 余分な引数
+  UnexpectedArgument(String),
+  /// オプションに値が指定されていない
+  MissingValue(&'static str),
+  /// オプションの値の形式が違う
+  InvalidValue(&'static str, String),
+  /// オプションの値が範囲外
+  OutOfRange {
+    /// オプションの名前
+    option: &'static str,
+    /// 指定できる最小の値
+    min: u64,
+    /// 指定できる最大の値
+    max: u64,
+  },
+  /// デイリーモードでは指定できないオプション
+  FixedInDaily(&'static str),
+  /// robotの数の上限がfieldに収まらない
+  TooManyRobots {
+    /// robotの数の上限
+    robots: usize,
+    /// robotを配置できるマスの数
+    cells: usize,
+  },
+}
+
+impl std::fmt::Display for CliError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      CliError::UnknownOption(option) => write!(f, "unknown option {}", option),
+      CliError::UnexpectedArgument(arg) => write!(f, "unexpected argument {}", arg),
+      CliError::MissingValue(option) => write!(f, "{} needs a value", option),
+      CliError::InvalidValue(option, value) => write!(f, "invalid value {} for {}", value, option),
+      CliError::OutOfRange { option, min, max } => write!(f, "{} must be between {} and {}", option, min, max),
+      CliError::FixedInDaily(option) => write!(f, "{} cannot be used with --daily", option),
+      CliError::TooManyRobots { robots, cells } =>
+        write!(f, "--max-robots {} does not fit in the {} cells left for robots", robots, cells),
+    }
+  }
+}
+
+impl std::error::Error for CliError {}
+
+/// オプションの値を取り出します
+/// * `args` - 残りの引数
+/// * `option` - オプションの名前
+fn value<'a, I: Iterator<Item = &'a String>>(args: &mut std::iter::Peekable<I>, option: &'static str) -> Result<&'a String, CliError> {
+  match args.next() {
+    Some(value) if !value.starts_with("--") => Ok(value),
+    _ => Err(CliError::MissingValue(option)),
+  }
+}
+
+/// 数値を解析し、範囲内か確認します
+/// * `option` - オプションの名前
+/// * `value` - 解析する文字列
+/// * `min` - 指定できる最小の値
+/// * `max` - 指定できる最大の値
+fn number<T: FromStr + Into<u64> + Copy>(option: &'static str, value: &str, min: u64, max: u64) -> Result<T, CliError> {
+  let n: T = value.parse().map_err(|_| CliError::InvalidValue(option, value.to_string()))?;
+  if n.into() < min || n.into() > max {
+    return Err(CliError::OutOfRange { option, min, max });
+  }
+  Ok(n)
+}
+
+/// `usize`の数値を解析し、範囲内か確認します
+/// * `option` - オプションの名前
+/// * `value` - 解析する文字列
+/// * `min` - 指定できる最小の値
+/// * `max` - 指定できる最大の値
+fn size(option: &'static str, value: &str, min: u64, max: u64) -> Result<usize, CliError> {
+  number::<u64>(option, value, min, max).map(|n| n as usize)
+}
+
+/// robotの数の上限がfieldに収まるかを調べます
+/// 協力プレイでは2人分のマスとその周りを空けます
+/// * `config` - 調べる設定
+pub fn check_robots(config: &Config) -> Result<(), CliError> {
+  let cells = Field::free_cells(config.width, config.height, config.coop, config.min_distance);
+  if config.max_robots() > cells {
+    return Err(CliError::TooManyRobots { robots: config.max_robots(), cells });
+  }
+  Ok(())
+}
+
+/// コマンドライン引数を解析します
+/// オプションはサブコマンドの前後どちらにも書けます
+/// * `args` - プログラム名を除いたコマンドライン引数
+pub fn parse(args: &[String]) -> Result<Cli, CliError> {
+  let mut cli = Cli {
+    command: None,
+    config: Config::default(),
+    ansi: false,
+    resume: false,
+    spectate: None,
+    help: false,
+  };
+  let mut positional = Vec::new();
+  let mut fixed_in_daily = None;
+  let mut args = args.iter().peekable();
+  while let Some(arg) = args.next() {
+    let config = &mut cli.config;
+    fixed_in_daily = fixed_in_daily.or_else(|| DAILY_FIXED_OPTIONS.iter().copied().find(|option| option == arg));
+    match arg.as_str() {
+      "--help" | "-h" => { cli.help = true; },
+      "--ansi" => { cli.ansi = true; },
+      "--resume" => { cli.resume = true; },
+      "--unicode" => {
+        let square = config.theme.square;
+        config.theme = Theme::unicode_for_locale();
+        config.theme.square = square;
+      },
+      "--square" => { config.theme.square = true; },
+      "--accessible" => { config.accessible = true; },
+      "--smart-robots" => { config.smart_robots = true; },
+      "--daily" => { config.daily = Some(daily::today()); },
+      "--profile" => {
+        let profile = value(&mut args, "--profile")?;
+        if !config::valid_profile(profile) {
+          return Err(CliError::InvalidValue("--profile", profile.clone()));
+        }
+        config.profile = profile.clone();
+      },
+      "--lang" => {
+        let lang = value(&mut args, "--lang")?;
+        config.lang = Lang::parse(lang).ok_or_else(|| CliError::InvalidValue("--lang", lang.clone()))?;
+      },
+      "--realtime" => {
+        // 間隔は省略できる
+        let ms = match args.next_if(|ms| ms.starts_with(|c: char| c.is_ascii_digit())) {
+          Some(ms) => number("--realtime", ms, 1, MAX_TICK_MS)?,
+          None => REALTIME_TICK_MS,
+        };
+        config.realtime = Some(ms);
+      },
+      "--spectate" => {
+        let addr = args.next_if(|addr| !addr.starts_with("--"));
+        cli.spectate = Some(addr.map_or_else(|| spectate::DEFAULT_PORT.to_string(), |addr| addr.clone()));
+      },
+      "--width" => { config.width = size("--width", value(&mut args, "--width")?, 1, MAX_FIELD_SIZE)?; },
+      "--height" => { config.height = size("--height", value(&mut args, "--height")?, 1, MAX_FIELD_SIZE)?; },
+      "--difficulty" => {
+        let name = value(&mut args, "--difficulty")?;
+        config.difficulty = Difficulty::parse(name).ok_or_else(|| CliError::InvalidValue("--difficulty", name.clone()))?;
+      },
+      "--robots" => {
+        config.robots_per_level = Some(size("--robots", value(&mut args, "--robots")?, 1, MAX_FIELD_SIZE * MAX_FIELD_SIZE)?);
+      },
+      "--max-robots" => {
+        config.max_robots = Some(size("--max-robots", value(&mut args, "--max-robots")?, 1, MAX_FIELD_SIZE * MAX_FIELD_SIZE)?);
+      },
+      "--min-distance" => {
+        config.min_distance = size("--min-distance", value(&mut args, "--min-distance")?, 1, MAX_FIELD_SIZE)?;
+      },
+      "--start-level" => {
+        config.start_level = number::<u32>("--start-level", value(&mut args, "--start-level")?, 1, MAX_START_LEVEL)?;
+      },
+      "--score-file" => { config.score_file = value(&mut args, "--score-file")?.clone(); },
+      "--seed" => { config.seed = Some(number("--seed", value(&mut args, "--seed")?, 0, u64::MAX)?); },
+      option if option.starts_with('-') && option.len() > 1 => {
+        return Err(CliError::UnknownOption(option.to_string()));
+      },
+      _ => positional.push(arg),
+    }
+  }
+
+  // デイリーモードは全員が同じfieldで遊ぶ
+  if let (Some(_), Some(option)) = (cli.config.daily, fixed_in_daily) {
+    return Err(CliError::FixedInDaily(option));
+  }
+
+  let mut positional = positional.into_iter();
+  let command = positional.next().map(|arg| arg.as_str());
+  let arg = positional.next();
+  let takes_arg = matches!(command, Some("host") | Some("watch") | Some("join") | Some("puzzle"));
+  if let Some(extra) = positional.next().or(arg.filter(|_| !takes_arg)) {
+    return Err(CliError::UnexpectedArgument(extra.clone()));
+  }
+  cli.command = match command {
+    None => None,
+    Some("stats") => Some(Command::Stats),
+    Some("achievements") => Some(Command::Achievements),
+    Some("daily") => Some(Command::Daily),
+    Some("coop") => {
+      cli.config.coop = true;
+      Some(Command::Coop)
+    },
+    Some("host") => Some(Command::Host(match arg {
+      Some(port) => number("host", port, 1, u16::MAX as u64)?,
+      None => net::DEFAULT_PORT,
+    })),
+    Some("watch") => Some(Command::Watch(arg.cloned().unwrap_or_else(|| "localhost".to_string()))),
+    Some("join") => Some(Command::Join(arg.cloned().unwrap_or_else(|| "localhost".to_string()))),
+    Some("puzzle") => Some(Command::Puzzle(match arg {
+      Some(n) => size("puzzle", n, 1, u32::MAX as u64)?,
+      None => 1,
+    })),
+    Some(other) => return Err(CliError::UnexpectedArgument(other.to_string())),
+  };
+  // 協力プレイかどうかはサブコマンドで決まるので、その後で調べる
+  check_robots(&cli.config)?;
+  Ok(cli)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(s: &str) -> Vec<String> {
+    s.split_whitespace().map(|a| a.to_string()).collect()
+  }
+
+#[test]
+  fn parse_test() {
+    let cli = parse(&args("--width 30 --height 20 --robots 3 --max-robots 12 --start-level 4 --seed 7 --score-file s.dat")).unwrap();
+    assert_eq!(cli.command, None);
+    assert_eq!((cli.config.width, cli.config.height), (30, 20));
+    assert_eq!((cli.config.robots_per_level(), cli.config.max_robots()), (3, 12));
+    assert_eq!(cli.config.start_level, 4);
+    assert_eq!(cli.config.seed, Some(7));
+    assert_eq!(cli.config.score_file, "s.dat");
+    assert_eq!(cli.config.min_distance, Config::default().min_distance);
+
+    let cli = parse(&args("--realtime host 9000 --square --spectate")).unwrap();
+    assert_eq!(cli.command, Some(Command::Host(9000)));
+    assert_eq!(cli.config.realtime, Some(REALTIME_TICK_MS));
+    assert_eq!(cli.config.width, Config::default().width);
+    assert_eq!(cli.spectate, Some(spectate::DEFAULT_PORT.to_string()));
+
+    let cli = parse(&args("--max-robots 50 --difficulty hard --smart-robots --min-distance 4")).unwrap();
+    assert!(cli.config.smart_robots);
+    assert_eq!(cli.config.min_distance, 4);
+    assert_eq!(cli.config.difficulty, Difficulty::Hard);
+    assert_eq!((cli.config.robots_per_level(), cli.config.max_robots()), (6, 50));
+
+    let cli = parse(&args("puzzle --realtime 500")).unwrap();
+    assert_eq!(cli.command, Some(Command::Puzzle(1)));
+    assert_eq!(cli.config.realtime, Some(500));
+  }
+
+#[test]
+  fn parse_error_test() {
+    assert_eq!(parse(&args("--width")).unwrap_err(), CliError::MissingValue("--width"));
+    assert_eq!(parse(&args("--width abc")).unwrap_err(), CliError::InvalidValue("--width", "abc".to_string()));
+    assert_eq!(parse(&args("--height 0")).unwrap_err(), CliError::OutOfRange { option: "--height", min: 1, max: MAX_FIELD_SIZE });
+    assert_eq!(parse(&args("--width 5 --height 5 --max-robots 25")).unwrap_err(), CliError::TooManyRobots { robots: 25, cells: 24 });
+    assert!(parse(&args("--width 5 --height 5 --max-robots 24")).is_ok());
+    assert_eq!(parse(&args("coop --width 5 --height 5 --max-robots 24")).unwrap_err(), CliError::TooManyRobots { robots: 24, cells: 23 });
+    assert_eq!(parse(&args("--width 9 --height 9 --max-robots 60 --min-distance 3")).unwrap_err(), CliError::TooManyRobots { robots: 60, cells: 56 });
+    assert_eq!(parse(&args("coop --width 9 --height 9 --max-robots 52 --min-distance 3")).unwrap_err(), CliError::TooManyRobots { robots: 52, cells: 51 });
+    // 1列のfieldは1人なら遊べるが、協力プレイでは2人を並べられない
+    assert!(parse(&args("--width 1 --height 50")).is_ok());
+    assert_eq!(parse(&args("coop --width 1 --height 50")).unwrap_err(), CliError::TooManyRobots { robots: 40, cells: 0 });
+    // 難易度を変えても同じ検証を使う
+    let config = Config { width: 9, height: 9, coop: true, difficulty: Difficulty::Nightmare, ..Config::default() };
+    assert_eq!(check_robots(&config), Err(CliError::TooManyRobots { robots: 80, cells: 79 }));
+    assert_eq!(check_robots(&Config { coop: false, ..config.clone() }), Ok(()));
+    assert_eq!(check_robots(&Config { max_robots: Some(60), ..config }), Ok(()));
+    assert_eq!(parse(&args("--difficulty insane")).unwrap_err(), CliError::InvalidValue("--difficulty", "insane".to_string()));
+    assert_eq!(parse(&args("--fast")).unwrap_err(), CliError::UnknownOption("--fast".to_string()));
+    assert_eq!(parse(&args("--daily --difficulty hard")).unwrap_err(), CliError::FixedInDaily("--difficulty"));
+    assert_eq!(parse(&args("--seed 3 --daily")).unwrap_err(), CliError::FixedInDaily("--seed"));
+    let spaced = vec!["--profile".to_string(), "alice smith".to_string()];
+    assert_eq!(parse(&spaced).unwrap_err(), CliError::InvalidValue("--profile", "alice smith".to_string()));
+    assert_eq!(parse(&args("play")).unwrap_err(), CliError::UnexpectedArgument("play".to_string()));
+    assert_eq!(parse(&args("watch a b")).unwrap_err(), CliError::UnexpectedArgument("b".to_string()));
+  }
+}
@@ -0,0 +1,204 @@
+use crate::theme::Theme;
+use crate::i18n::Lang;
+use crate::difficulty::Difficulty;
+use crate::internal::Placement;
+
+/// fieldの横幅と縦幅の上限
+pub const MAX_FIELD_SIZE: usize = 1000;
+/// リアルタイムモードでrobotが動く間隔の下限(ミリ秒)
+const MIN_TICK_MS: u64 = 150;
+/// リアルタイムモードでlevel 1のrobotが動くデフォルトの間隔(ミリ秒)
+pub const REALTIME_TICK_MS: u64 = 1000;
+/// smart_robotが現れるlevelで、super_robot以外のrobotの何体に1体をsmart_robotにするか
+const SMART_ROBOT_RATIO: usize = 4;
+
+/// プロフィール名として使えるかを返します
+/// 実績やデイリーモードのファイルは空白区切りなので、空白を含む名前は使えません
+/// * `name` - プロフィール名
+pub fn valid_profile(name: &str) -> bool {
+  !name.is_empty() && !name.contains(char::is_whitespace)
+}
+
+/// ゲームの設定を表します
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+  /// fieldの横幅
+  pub width: usize,
+  /// fieldの縦幅
+  pub height: usize,
+  /// 2人で同じfieldを協力してプレイするか
+  pub coop: bool,
+  /// デイリーモードで遊ぶ日(1970-01-01からの日数)
+  pub daily: Option<u64>,
+  /// 実績を記録するプロフィール名
+  pub profile: String,
+  /// リアルタイムモードでlevel 1のrobotが動く間隔(ミリ秒)
+  pub realtime: Option<u64>,
+  /// fieldの描画に使う記号
+  pub theme: Theme,
+  /// 毎ターンfieldの状況を文章で表示するか
+  pub accessible: bool,
+  /// 画面に表示する言語
+  pub lang: Lang,
+  /// 難易度
+  pub difficulty: Difficulty,
+  /// levelが1上がるごとに増えるrobotの数(指定しない場合は難易度の設定を使う)
+  pub robots_per_level: Option<usize>,
+  /// 1つのlevelのrobotの数の上限(指定しない場合は難易度の設定を使う)
+  pub max_robots: Option<usize>,
+  /// 難易度に関わらずlevel 1からsmart_robotを配置するか
+  pub smart_robots: bool,
+  /// robotとplayerの初期位置の最小の距離
+  pub min_distance: usize,
+  /// 最初のlevel
+  pub start_level: u32,
+  /// ハイスコアを記録するファイル名
+  pub score_file: String,
+  /// fieldの生成とランダム移動に使う乱数のシード(指定しない場合は毎回変わる)
+  pub seed: Option<u64>,
+}
+
+impl Default for Config {
+  fn default() -> Config {
+    Config {
+      width: 100,
+      height: 40,
+      coop: false,
+      daily: None,
+      profile: std::env::var("USER").ok().filter(|user| valid_profile(user)).unwrap_or_else(|| "player".to_string()),
+      realtime: None,
+      theme: Theme::ascii(),
+      accessible: false,
+      lang: Lang::from_env(),
+      difficulty: Difficulty::Normal,
+      robots_per_level: None,
+      max_robots: None,
+      smart_robots: false,
+      min_distance: 1,
+      start_level: 1,
+      score_file: "score.dat".to_string(),
+      seed: None,
+    }
+  }
+}
+
+impl Config {
+  /// levelが1上がるごとに増えるrobotの数を返します
+  /// 指定していない場合は難易度の設定を使います
+  pub fn robots_per_level(&self) -> usize {
+    self.robots_per_level.unwrap_or(self.difficulty.preset().robots_per_level)
+  }
+
+  /// 1つのlevelのrobotの数の上限を返します
+  /// 指定していない場合は難易度の設定を使います
+  pub fn max_robots(&self) -> usize {
+    self.max_robots.unwrap_or(self.difficulty.preset().max_robots)
+  }
+
+  /// 指定のlevelに配置するrobotの数を返します
+  /// * `level` - 現在のlevel
+  pub fn robots(&self, level: u32) -> usize {
+    std::cmp::min(self.robots_per_level().saturating_mul(level as usize), self.max_robots())
+  }
+
+  /// 指定の難易度とlevelでrobotを配置するときの条件を返します
+  /// * `difficulty` - 難易度
+  /// * `level` - 現在のlevel
+  pub fn placement(&self, difficulty: Difficulty, level: u32) -> Placement {
+    let preset = difficulty.preset();
+    let smart = self.smart_robots || preset.smart_robots_from.is_some_and(|from| level >= from);
+    Placement {
+      super_robot_ratio: preset.super_robot_ratio,
+      smart_robot_ratio: if smart { SMART_ROBOT_RATIO } else { 0 },
+      min_distance: self.min_distance,
+    }
+  }
+
+  /// デイリーモードで遊ぶための設定を返します
+  /// 同じ日の全員が同じfieldで遊べるように、fieldとrobotに関わる設定を標準の値に戻します
+  /// 表示や記録に関わる設定はそのまま使います
+  pub fn for_daily(&self) -> Config {
+    let standard = Config::default();
+    Config {
+      width: standard.width,
+      height: standard.height,
+      coop: false,
+      realtime: None,
+      difficulty: standard.difficulty,
+      robots_per_level: standard.robots_per_level,
+      max_robots: standard.max_robots,
+      smart_robots: standard.smart_robots,
+      min_distance: standard.min_distance,
+      start_level: standard.start_level,
+      seed: None,
+      ..self.clone()
+    }
+  }
+
+  /// リアルタイムモードで指定のlevelのrobotが動く間隔(ミリ秒)を返します
+  /// levelが1上がるごとに間隔は15%短くなります
+  /// リアルタイムモードでない場合は`None`を返します
+  /// * `level` - 現在のlevel
+  pub fn tick_ms(&self, level: u32) -> Option<u64> {
+    let mut tick = self.realtime?;
+    for _ in 1..level {
+      tick = tick * 85 / 100;
+    }
+    Some(std::cmp::max(tick, MIN_TICK_MS))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn tick_ms_test() {
+    let mut config = Config::default();
+    assert_eq!(config.tick_ms(1), None);
+    config.realtime = Some(1000);
+    assert_eq!(config.tick_ms(1), Some(1000));
+    assert_eq!(config.tick_ms(2), Some(850));
+    assert_eq!(config.tick_ms(3), Some(722));
+    assert_eq!(config.tick_ms(50), Some(MIN_TICK_MS));
+  }
+
+#[test]
+  fn robots_test() {
+    let mut config = Config::default();
+    assert_eq!(config.robots(1), 5);
+    assert_eq!(config.robots(8), 40);
+    assert_eq!(config.robots(9), 40);
+    config.difficulty = Difficulty::Hard;
+    assert_eq!(config.robots(20), 60);
+    config.robots_per_level = Some(3);
+    config.max_robots = Some(10);
+    assert_eq!(config.robots(2), 6);
+    assert_eq!(config.robots(4), 10);
+    // 指定した値は難易度を変えても使われる
+    config.difficulty = Difficulty::Easy;
+    assert_eq!((config.robots_per_level(), config.max_robots()), (3, 10));
+  }
+
+#[test]
+  fn placement_test() {
+    let mut config = Config::default();
+    assert_eq!(config.placement(Difficulty::Normal, 20).smart_robot_ratio, 0);
+    assert_eq!(config.placement(Difficulty::Nightmare, 3).smart_robot_ratio, 0);
+    assert_eq!(config.placement(Difficulty::Nightmare, 4).smart_robot_ratio, SMART_ROBOT_RATIO);
+    config.smart_robots = true;
+    assert_eq!(config.placement(Difficulty::Easy, 1), Placement { super_robot_ratio: 20, smart_robot_ratio: SMART_ROBOT_RATIO, min_distance: 1 });
+    config.min_distance = 5;
+    assert_eq!(config.placement(Difficulty::Normal, 1).min_distance, 5);
+  }
+
+#[test]
+  fn for_daily_test() {
+    let config = Config {
+      width: 30, height: 10, difficulty: Difficulty::Nightmare, max_robots: Some(20), min_distance: 4, start_level: 7,
+      daily: Some(100), profile: "alice".to_string(), ..Config::default()
+    };
+    let daily = config.for_daily();
+    assert_eq!(daily, Config { profile: "alice".to_string(), daily: Some(100), ..Config::default() });
+  }
+}
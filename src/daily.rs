@@ -0,0 +1,152 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// 1日の秒数
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// 今日の日付を1970-01-01からの日数(UTC)で返します
+pub fn today() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() / SECS_PER_DAY).unwrap_or(0)
+}
+
+/// 1970-01-01からの日数を`YYYY-MM-DD`形式の日付にします
+/// * `day` - 1970-01-01からの日数
+pub fn date_string(day: u64) -> String {
+  // 3月始まりの400年周期で年月日を求める
+  let z = day + 719_468;
+  let era = z / 146_097;
+  let doe = z % 146_097;
+  let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let d = doy - (153 * mp + 2) / 5 + 1;
+  let m = if mp < 10 { mp + 3 } else { mp - 9 };
+  let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+  format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// その日の全員が共通で使う乱数生成器を返します
+/// levelごとに別のストリームを使い、ランダム移動にはストリーム0を使います
+/// * `day` - 1970-01-01からの日数
+/// * `stream` - 乱数のストリーム番号(levelまたは0)
+pub fn rng(day: u64, stream: u32) -> ChaCha8Rng {
+  let mut rng = ChaCha8Rng::seed_from_u64(day);
+  rng.set_stream(stream as u64);
+  rng
+}
+
+/// デイリーモードの1回分の記録を表します
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DailyEntry {
+  /// プレイした日付(`YYYY-MM-DD`)
+  pub date: String,
+  /// プロフィール名
+  pub profile: String,
+  /// 獲得したscore
+  pub score: u64,
+}
+
+/// デイリーモードのハイスコア表を表します
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DailyTable {
+  /// 全ての日の記録
+  pub entries: Vec<DailyEntry>,
+}
+
+impl DailyTable {
+  /// ハイスコア表をファイルから読み込みます
+  /// ファイルが無い場合や読み込めない行は無視します
+  /// * `filename` - ハイスコア表のファイル名
+  pub fn load(filename: &str) -> DailyTable {
+    let file = fs::read_to_string(filename).unwrap_or_default();
+    let entries = file.lines().filter_map(|line| {
+      let cols: Vec<&str> = line.split_whitespace().collect();
+      if cols.len() != 3 {
+        return None;
+      }
+      Some(DailyEntry { date: cols[0].to_string(), profile: cols[1].to_string(), score: cols[2].parse().ok()? })
+    }).collect();
+    DailyTable { entries }
+  }
+
+  /// ハイスコア表をファイルに保存します
+  /// * `filename` - ハイスコア表のファイル名
+  pub fn save(&self, filename: &str) -> std::io::Result<()> {
+    let lines: Vec<String> = self.entries.iter()
+      .map(|e| format!("{} {} {}\n", e.date, e.profile, e.score))
+      .collect();
+    fs::write(filename, lines.concat())
+  }
+
+  /// その日にランキング対象のプレイを済ませたかを判定します
+  /// * `date` - 日付
+  /// * `profile` - プロフィール名
+  pub fn has_played(&self, date: &str, profile: &str) -> bool {
+    self.entries.iter().any(|e| e.date == date && e.profile == profile)
+  }
+
+  /// その日の記録を書き込みます
+  /// 既に記録がある場合は置き換えます
+  /// * `date` - 日付
+  /// * `profile` - プロフィール名
+  /// * `score` - 獲得したscore
+  pub fn record(&mut self, date: &str, profile: &str, score: u64) {
+    match self.entries.iter_mut().find(|e| e.date == date && e.profile == profile) {
+      Some(entry) => { entry.score = score; },
+      None => self.entries.push(DailyEntry { date: date.to_string(), profile: profile.to_string(), score }),
+    }
+  }
+
+  /// その日の記録をscoreの高い順に返します
+  /// * `date` - 日付
+  pub fn ranking(&self, date: &str) -> Vec<&DailyEntry> {
+    let mut ranking: Vec<&DailyEntry> = self.entries.iter().filter(|e| e.date == date).collect();
+    ranking.sort_by_key(|e| std::cmp::Reverse(e.score));
+    ranking
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::Rng;
+
+#[test]
+  fn date_string_test() {
+    assert_eq!(date_string(0), "1970-01-01");
+    assert_eq!(date_string(59), "1970-03-01");
+    assert_eq!(date_string(11_016), "2000-02-29");
+    assert_eq!(date_string(19_723), "2024-01-01");
+  }
+
+#[test]
+  fn rng_test() {
+    assert_eq!(rng(100, 1).gen::<u64>(), rng(100, 1).gen::<u64>());
+    assert_ne!(rng(100, 1).gen::<u64>(), rng(101, 1).gen::<u64>());
+    assert_ne!(rng(100, 1).gen::<u64>(), rng(100, 2).gen::<u64>());
+  }
+
+#[test]
+  fn table_test() {
+    let path = std::env::temp_dir().join(format!("robots-daily-{}.dat", std::process::id()));
+    let filename = path.to_str().unwrap();
+    let _ = fs::remove_file(filename);
+
+    let mut table = DailyTable::load(filename);
+    assert!(!table.has_played("2024-01-01", "alice"));
+    table.record("2024-01-01", "alice", 0);
+    table.record("2024-01-01", "bob", 30);
+    table.record("2024-01-02", "carol", 99);
+    table.record("2024-01-01", "alice", 50);
+    table.save(filename).unwrap();
+
+    let table = DailyTable::load(filename);
+    assert!(table.has_played("2024-01-01", "alice"));
+    assert!(!table.has_played("2024-01-02", "alice"));
+    let ranking: Vec<(&str, u64)> = table.ranking("2024-01-01").iter().map(|e| (e.profile.as_str(), e.score)).collect();
+    assert_eq!(ranking, vec![("alice", 50), ("bob", 30)]);
+    fs::remove_file(filename).unwrap();
+  }
+}
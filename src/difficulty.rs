@@ -0,0 +1,166 @@
+/// 難易度を表します
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Difficulty {
+  /// robotが少なく、倒される移動を止めてくれる
+  Easy,
+  /// 標準の難易度
+  #[default]
+  Normal,
+  /// robotが多く、ランダム移動の回数に制限がある
+  Hard,
+  /// super_robotが多く、ランダム移動もほとんど使えない
+  Nightmare,
+}
+
+/// 難易度ごとのゲームの設定を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Preset {
+  /// levelが1上がるごとに増えるrobotの数
+  pub robots_per_level: usize,
+  /// 1つのlevelのrobotの数の上限
+  pub max_robots: usize,
+  /// robotの何体に1体をsuper_robotにするか
+  pub super_robot_ratio: usize,
+  /// smart_robotが現れ始めるlevel(`None`は現れない)
+  pub smart_robots_from: Option<u32>,
+  /// 1つのlevelで使えるランダム移動の回数(`None`は無制限)
+  pub teleports: Option<u32>,
+  /// 次のターンに倒される移動を止めるか
+  pub safe_moves: bool,
+  /// 獲得するscoreの倍率(パーセント)
+  pub score_percent: u64,
+}
+
+impl Difficulty {
+  /// すべての難易度(易しい順)
+  pub const ALL: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Nightmare];
+
+  /// 名前から難易度を返します
+  /// 知らない名前の場合は`None`を返します
+  /// * `s` - 難易度の名前
+  pub fn parse(s: &str) -> Option<Difficulty> {
+    Difficulty::ALL.iter().copied().find(|d| d.name() == s)
+  }
+
+  /// ファイルやコマンドラインで使う難易度の名前を返します
+  pub fn name(&self) -> &'static str {
+    match self {
+      Difficulty::Easy => "easy",
+      Difficulty::Normal => "normal",
+      Difficulty::Hard => "hard",
+      Difficulty::Nightmare => "nightmare",
+    }
+  }
+
+  /// 次に難しい難易度を返します
+  /// 最も難しい場合は最も易しい難易度に戻ります
+  pub fn next(&self) -> Difficulty {
+    let idx = Difficulty::ALL.iter().position(|d| d == self).unwrap_or(0);
+    Difficulty::ALL[(idx + 1) % Difficulty::ALL.len()]
+  }
+
+  /// 難易度の設定を返します
+  pub fn preset(&self) -> Preset {
+    match self {
+      Difficulty::Easy => Preset {
+        robots_per_level: 3,
+        max_robots: 25,
+        super_robot_ratio: 20,
+        smart_robots_from: None,
+        teleports: None,
+        safe_moves: true,
+        score_percent: 50,
+      },
+      Difficulty::Normal => Preset {
+        robots_per_level: 5,
+        max_robots: 40,
+        super_robot_ratio: 10,
+        smart_robots_from: None,
+        teleports: None,
+        safe_moves: false,
+        score_percent: 100,
+      },
+      Difficulty::Hard => Preset {
+        robots_per_level: 6,
+        max_robots: 60,
+        super_robot_ratio: 5,
+        smart_robots_from: Some(8),
+        teleports: Some(5),
+        safe_moves: false,
+        score_percent: 150,
+      },
+      Difficulty::Nightmare => Preset {
+        robots_per_level: 8,
+        max_robots: 80,
+        super_robot_ratio: 3,
+        smart_robots_from: Some(4),
+        teleports: Some(2),
+        safe_moves: false,
+        score_percent: 200,
+      },
+    }
+  }
+}
+
+/// ゲーム中の難易度と、現在のlevelで使えるランダム移動の残り回数を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rules {
+  /// 難易度
+  pub difficulty: Difficulty,
+  /// 現在のlevelで使えるランダム移動の残り回数(`None`は無制限)
+  pub teleports_left: Option<u32>,
+}
+
+impl Rules {
+  /// 指定の難易度でゲームを始めるときの状態を生成します
+  /// * `difficulty` - 難易度
+  pub fn new(difficulty: Difficulty) -> Rules {
+    Rules { difficulty, teleports_left: difficulty.preset().teleports }
+  }
+
+  /// levelの開始時にランダム移動の回数を戻します
+  pub fn start_level(&mut self) {
+    self.teleports_left = self.difficulty.preset().teleports;
+  }
+
+  /// ランダム移動を使えるかを返します
+  pub fn can_teleport(&self) -> bool {
+    self.teleports_left != Some(0)
+  }
+
+  /// ランダム移動を使ったことを記録します
+  pub fn teleport(&mut self) {
+    if let Some(left) = &mut self.teleports_left {
+      *left = left.saturating_sub(1);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn parse_test() {
+    for d in Difficulty::ALL.iter() {
+      assert_eq!(Difficulty::parse(d.name()), Some(*d));
+    }
+    assert_eq!(Difficulty::parse("insane"), None);
+    assert_eq!(Difficulty::Nightmare.next(), Difficulty::Easy);
+  }
+
+#[test]
+  fn rules_test() {
+    let mut rules = Rules::new(Difficulty::Nightmare);
+    assert!(rules.can_teleport());
+    rules.teleport();
+    rules.teleport();
+    assert!(!rules.can_teleport());
+    rules.start_level();
+    assert_eq!(rules.teleports_left, Some(2));
+
+    let mut rules = Rules::new(Difficulty::Normal);
+    rules.teleport();
+    assert!(rules.can_teleport());
+  }
+}
@@ -0,0 +1,68 @@
+use std::convert::TryFrom;
+use crate::internal::*;
+use crate::theme::Theme;
+use crate::i18n::{Lang, Msg};
+
+/// キーを画面に表示する名前を返します
+/// * `key` - キー
+fn key_name(key: i32) -> String {
+  match u8::try_from(key) {
+    Ok(b' ') => "space".to_string(),
+    Ok(c) if c.is_ascii_graphic() => (c as char).to_string(),
+    _ => "?".to_string(),
+  }
+}
+
+/// 指定の操作に割り当てられたキーの名前を返します
+/// 割り当てられていない場合は`-`を返します
+/// * `keymap` - キー配置
+/// * `action` - 操作(`KEY_*`)
+fn key_for(keymap: &KeyMap, action: i32) -> String {
+  keymap.keys.iter().find(|(_, a)| *a == action).map_or("-".to_string(), |(key, _)| key_name(*key))
+}
+
+/// キー配置の説明と記号の凡例を返します
+/// 8方向の移動キーはplayerを中心にした図で示します
+/// * `keymap` - 説明するキー配置
+/// * `theme` - fieldの描画に使う記号
+/// * `lang` - 表示する言語
+pub fn lines(keymap: &KeyMap, theme: &Theme, lang: Lang) -> Vec<String> {
+  let k = |action| format!("{:^5}", key_for(keymap, action));
+  vec![
+    Msg::HelpMove.text(lang),
+    format!("  {}{}{}", k(KEY_LUP), k(KEY_UP), k(KEY_RUP)),
+    format!("  {}{:^5}{}", k(KEY_LEFT), theme.player, k(KEY_RIGHT)),
+    format!("  {}{}{}", k(KEY_LDOWN), k(KEY_DOWN), k(KEY_RDOWN)),
+    String::new(),
+    format!("  {:<10}{}", key_for(keymap, KEY_STAY), Msg::HelpStay.text(lang)),
+    format!("  {:<10}{}", key_for(keymap, KEY_RAND), Msg::HelpTeleport.text(lang)),
+    format!("  {:<10}{}", key_for(keymap, KEY_STOP), Msg::HelpStop.text(lang)),
+    format!("  {:<10}{}", key_for(keymap, KEY_QUIT), Msg::HelpQuit.text(lang)),
+    String::new(),
+    Msg::HelpLegend.text(lang),
+    format!("  {}  {}", theme.player, Msg::LegendPlayer.text(lang)),
+    format!("  {}  {}", theme.partner, Msg::LegendPartner.text(lang)),
+    format!("  {}  {}", theme.robot, Msg::LegendRobot.text(lang)),
+    format!("  {}  {}", theme.super_robot, Msg::LegendSuperRobot.text(lang)),
+    format!("  {}  {}", theme.smart_robot, Msg::LegendSmartRobot.text(lang)),
+    format!("  {}  {}", theme.scrap, Msg::LegendScrap.text(lang)),
+  ]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn lines_test() {
+    let help = lines(&KeyMap::player1(), &Theme::ascii(), Lang::En);
+    assert_eq!(help[1], "    u    i    o  ");
+    assert_eq!(help[2], "    j    @    l  ");
+    assert_eq!(help[3], "    m    ,    .  ");
+    assert_eq!(help[5], "  space     stay");
+
+    let help = lines(&KeyMap::player2(), &Theme::ascii(), Lang::Ja);
+    assert_eq!(help[1], "    w    e    r  ");
+    assert_eq!(help[6], "  d         ランダムなマスに移動");
+  }
+}
@@ -0,0 +1,773 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use crate::accessible::{Direction, NeighbourState};
+use crate::achievements::Achievement;
+use crate::cli::CliError;
+use crate::difficulty::Difficulty;
+use crate::internal::{FieldError, Object};
+use crate::save::SaveError;
+use crate::score::ScoreBreakdown;
+use crate::stats::Stat;
+
+/// 画面に表示する言語を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Lang {
+  /// 英語
+  En,
+  /// 日本語
+  Ja,
+}
+
+impl Lang {
+  /// 環境変数`LC_ALL`, `LC_MESSAGES`, `LANG`から言語を決めます
+  /// 最初に設定されている変数が`ja`で始まる場合は日本語、それ以外は英語を返します
+  pub fn from_env() -> Lang {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+      .filter_map(|name| std::env::var(name).ok())
+      .find(|value| !value.is_empty());
+    match locale {
+      Some(locale) if locale.starts_with("ja") => Lang::Ja,
+      _ => Lang::En,
+    }
+  }
+
+  /// `en`または`ja`から言語を返します
+  /// それ以外の場合は`None`を返します
+  /// * `s` - 言語の名前
+  pub fn parse(s: &str) -> Option<Lang> {
+    match s {
+      "en" => Some(Lang::En),
+      "ja" => Some(Lang::Ja),
+      _ => None,
+    }
+  }
+
+  /// 言語の名前をその言語で返します
+  pub fn name(&self) -> &'static str {
+    match self {
+      Lang::En => "English",
+      Lang::Ja => "日本語",
+    }
+  }
+}
+
+/// 文字列を端末に表示したときの列数を返します
+/// 全角の文字は2列と数えます
+/// * `s` - 表示する文字列
+pub fn width(s: &str) -> usize {
+  s.chars().map(|c| match c as u32 {
+    0x1100..=0x115f | 0x2e80..=0xa4cf | 0xac00..=0xd7a3 | 0xf900..=0xfaff |
+    0xfe30..=0xfe4f | 0xff00..=0xff60 | 0xffe0..=0xffe6 => 2,
+    _ => 1,
+  }).sum()
+}
+
+/// 表示したときの列数が`cols`になるまで文字列の後ろを空白で埋めます
+/// * `s` - 表示する文字列
+/// * `cols` - 埋めた後の列数
+pub fn pad(s: &str, cols: usize) -> String {
+  format!("{}{}", s, " ".repeat(cols.saturating_sub(width(s))))
+}
+
+/// 画面に表示するメッセージを表します
+pub enum Msg<'a> {
+  /// 現在のlevelとscore
+  Status(u32, u64),
+  /// コマンドライン引数が正しくない
+  CliError(&'a CliError),
+  /// `--help`で使い方を確認するよう促す
+  UsageHint,
+  /// コマンドラインの使い方
+  Usage,
+  /// 端末を初期化できなかった
+  TerminalInitFailed(&'a dyn Display),
+  /// 対戦相手を待っている
+  WaitingForOpponent(u16),
+  /// 対戦を待ち受けられなかった
+  HostFailed(&'a dyn Display),
+  /// 対戦相手に接続できなかった
+  JoinFailed(&'a str, &'a dyn Display),
+  /// 観戦を受け付けている
+  SpectateReady(&'a str),
+  /// 観戦を受け付けられなかった
+  SpectateFailed(&'a str, &'a dyn Display),
+  /// 中断したゲームを再開するかの確認
+  ResumePrompt,
+  /// 中断したゲームを読み込めなかった
+  LoadFailed(&'a SaveError),
+  /// 壊れたセーブファイルを削除するかの確認
+  DeleteSavePrompt,
+  /// 累計の記録を保存できなかった
+  StatsSaveFailed(&'a dyn Display),
+  /// キー入力を待っていることを示す
+  PressAnyKey,
+  /// 元の画面に戻るキー入力を待っていることを示す
+  PressAnyKeyToReturn,
+  /// メニューの操作方法
+  MenuHint,
+  /// タイトル画面の項目: 新しいゲーム
+  NewGame,
+  /// タイトル画面の項目: 再開
+  Continue,
+  /// タイトル画面の項目: 遊び方と現在の遊び方
+  GameMode(&'a str),
+  /// 遊び方を選ぶメニューの見出し
+  GameModeTitle,
+  /// タイトル画面の項目: ハイスコア
+  HighScores,
+  /// タイトル画面の項目: 設定
+  Options,
+  /// メニューの項目: 終了
+  Quit,
+  /// 再開できるゲームが無い
+  NoSavedGame,
+  /// 遊び方の名前(0: 通常, 1: リアルタイム, 2: デイリー, 3: 協力プレイ, 4: パズル)
+  Mode(usize),
+  /// 設定の項目: Unicodeのテーマ
+  OptionUnicode(bool),
+  /// 設定の項目: 1マスを2列で描画する
+  OptionSquare(bool),
+  /// 設定の項目: アクセシブルモード
+  OptionAccessible(bool),
+  /// 設定の項目: 言語
+  OptionLanguage(Lang),
+  /// 設定の項目: 難易度
+  OptionDifficulty(Difficulty),
+  /// 難易度の名前
+  Difficulty(Difficulty),
+  /// メニューの項目: 戻る
+  Back,
+  /// ハイスコアの画面の見出し
+  HighScoresTitle,
+  /// 記録が無い
+  NoRecords,
+  /// スコアファイルの形式が違う
+  ScoreFileCorrupt,
+  /// スコアファイルに書き込めなかった
+  ScoreSaveFailed(&'a dyn Display),
+  /// 一時停止の見出し
+  Paused,
+  /// 一時停止の項目: ゲームに戻る
+  Resume,
+  /// 一時停止の項目: 操作方法
+  Help,
+  /// 一時停止の項目: 累計の記録
+  Statistics,
+  /// 一時停止の項目: 実績
+  Achievements,
+  /// 観戦を始められなかった
+  WatchFailed(&'a str, &'a dyn Display),
+  /// 観戦中
+  Watching(&'a str),
+  /// 観戦中のゲームが終わった
+  GameEnded,
+  /// パズルの番号と名前
+  PuzzleTitle(usize, usize, &'a str),
+  /// パズルで使ったターン数と目標のターン数
+  Moves(u32, u32),
+  /// パズルモードではランダム移動を使えない
+  TeleportDisabled,
+  /// 現在のlevelで使えるランダム移動の残り回数
+  TeleportsLeft(u32),
+  /// 現在のlevelではもうランダム移動を使えない
+  NoTeleportsLeft,
+  /// 次のターンに倒される移動を止めた
+  UnsafeMove,
+  /// パズルが解けた(ターン数, 目標との比較)
+  Solved(u32, Ordering),
+  /// パズルを解けなかった
+  PuzzleFailed(u32),
+  /// 全てのパズルを遊んだ
+  AllPuzzlesPlayed,
+  /// デイリーモードのランキングを保存できなかった
+  DailySaveFailed,
+  /// その日のデイリーモードを既に遊んでいる
+  AlreadyPlayedDaily(&'a str),
+  /// デイリーモードのランキングの見出し
+  DailyRanking(&'a str),
+  /// デイリーモードの自分のscore(score, ランキングの対象か)
+  YourScore(u64, bool),
+  /// fieldを生成できなかった
+  CannotCreateField(u32, &'a FieldError),
+  /// fieldの生成やplayerの移動に失敗した理由
+  FieldError(&'a FieldError),
+  /// 対戦相手が先に負けた
+  OpponentDown,
+  /// 保存して終了するかの確認
+  SavePrompt,
+  /// ゲームを保存できなかった
+  SaveFailed,
+  /// 協力プレイで指定の番号のplayerが負けた
+  PlayerDown(usize),
+  /// 対戦で相手より先に負けた
+  YouLoseToOpponent,
+  /// 対戦で相手が先に負けていた
+  OpponentWentDownFirst,
+  /// 負けた
+  YouLose,
+  /// levelをクリアした
+  YouWin,
+  /// 累計の記録の見出し
+  StatisticsTitle,
+  /// 累計の記録の項目名
+  Stat(Stat),
+  /// 新しく獲得した実績
+  AchievementUnlocked(&'a str),
+  /// 実績を保存できなかった
+  AchievementsSaveFailed,
+  /// 実績の一覧の見出し
+  AchievementsTitle(&'a str),
+  /// 実績の名前
+  AchievementTitle(Achievement),
+  /// 実績の条件
+  AchievementDescription(Achievement),
+  /// 操作方法の見出し
+  HelpTitle,
+  /// 操作方法の画面で示すゲーム以外のキー
+  HelpExtraKeys,
+  /// 操作方法: 移動
+  HelpMove,
+  /// 操作方法: 移動しない
+  HelpStay,
+  /// 操作方法: ランダム移動
+  HelpTeleport,
+  /// 操作方法: これ以降は動かない
+  HelpStop,
+  /// 操作方法: 終了
+  HelpQuit,
+  /// 記号の凡例の見出し
+  HelpLegend,
+  /// 凡例: player
+  LegendPlayer,
+  /// 凡例: 2人目のplayer
+  LegendPartner,
+  /// 凡例: robot
+  LegendRobot,
+  /// 凡例: super_robot
+  LegendSuperRobot,
+  /// 凡例: smart_robot
+  LegendSmartRobot,
+  /// 凡例: scrap
+  LegendScrap,
+  /// 協力プレイで入力するplayer(番号, 記号)
+  PlayerToMove(usize, char),
+  /// 対戦相手のlevelとscore
+  OpponentStatus(u32, u64),
+  /// 対戦相手との接続が切れた
+  OpponentDisconnected,
+  /// 直前のターンのscoreの内訳
+  Breakdown(&'a ScoreBreakdown),
+  /// ハイスコアを更新した
+  HighScore(u64),
+  /// 説明: playerが負けている
+  SummaryDown(usize),
+  /// 説明: playerの座標と残りの数(番号, 列, 行, robot, super_robot, scrap)
+  SummaryPosition(usize, usize, usize, usize, usize, usize),
+  /// 説明: robotが残っていない
+  NoRobotsLeft,
+  /// 説明: 近いrobotの一覧
+  Nearest(&'a str),
+  /// 説明: 近いrobot(super_robotか, 距離, 方向)
+  Threat(bool, usize, Direction),
+  /// 説明: 周囲のマスの状態(方向, 状態)
+  Neighbour(Direction, NeighbourState),
+}
+
+impl Msg<'_> {
+  /// 指定の言語でメッセージを返します
+  /// * `lang` - 表示する言語
+  pub fn text(&self, lang: Lang) -> String {
+    match lang {
+      Lang::En => self.en(),
+      Lang::Ja => self.ja(),
+    }
+  }
+
+  /// 英語のメッセージを返します
+  fn en(&self) -> String {
+    match self {
+      Msg::Status(level, score) => format!("level: {}, score: {}", level, score),
+      Msg::CliError(e) => format!("robots: {}", e),
+      Msg::UsageHint => "run `robots --help` for usage".to_string(),
+      Msg::Usage => [
+        "usage: robots [options] [command]",
+        "",
+        "commands:",
+        "  host [port]          wait for an opponent",
+        "  join [address]       play against the opponent at address",
+        "  watch [address]      watch the game at address",
+        "  coop                 play with two players on one field",
+        "  puzzle [n]           play the puzzles from number n",
+        "  daily                show today's daily ranking",
+        "  stats                show the statistics",
+        "  achievements         show the achievements",
+        "",
+        "options:",
+        "  --width <n>          field width (default 100)",
+        "  --height <n>         field height (default 40)",
+        "  --robots <n>         robots added per level (default 5)",
+        "  --max-robots <n>     maximum number of robots (default 40)",
+        "  --difficulty <name>  easy, normal, hard or nightmare (default normal)",
+        "  --smart-robots       add smart robots that avoid scrap from level 1",
+        "  --min-distance <n>   minimum distance from the player to robots at the start (default 1)",
+        "  --start-level <n>    level to start from (default 1)",
+        "  --score-file <file>  file to record high scores (default score.dat)",
+        "  --seed <n>           random seed for the fields and teleports",
+        "  --realtime [ms]      move robots every ms milliseconds",
+        "  --daily              play today's daily challenge",
+        "  --resume             resume the saved game without asking",
+        "  --spectate [addr]    let spectators watch at addr",
+        "  --profile <name>     profile to record achievements (no spaces)",
+        "  --unicode            draw the field with Unicode symbols",
+        "  --square             draw each cell two columns wide",
+        "  --accessible         describe the field in text every turn",
+        "  --lang <en|ja>       language of the messages",
+        "  --ansi               draw with ANSI escape sequences instead of ncurses",
+        "  -h, --help           show this help",
+      ].join("\n"),
+      Msg::TerminalInitFailed(e) => format!("failed to initialize the terminal: {}", e),
+      Msg::WaitingForOpponent(port) => format!("waiting for an opponent on port {} ...", port),
+      Msg::HostFailed(e) => format!("failed to host a game: {}", e),
+      Msg::JoinFailed(addr, e) => format!("failed to join {}: {}", addr, e),
+      Msg::SpectateReady(addr) => format!("spectators can watch at {}", addr),
+      Msg::SpectateFailed(addr, e) => format!("failed to start spectating on {}: {}", addr, e),
+      Msg::ResumePrompt => "saved game found. resume? (y/n)".to_string(),
+      Msg::LoadFailed(e) => format!("failed to load saved game: {}", e),
+      Msg::DeleteSavePrompt => "delete the broken save? (y/n)".to_string(),
+      Msg::StatsSaveFailed(e) => format!("failed to save statistics: {}", e),
+      Msg::PressAnyKey => "(press any key)".to_string(),
+      Msg::PressAnyKeyToReturn => "press any key to return".to_string(),
+      Msg::MenuHint => "i/,: move, space/enter: select, q: back".to_string(),
+      Msg::NewGame => "New Game".to_string(),
+      Msg::Continue => "Continue".to_string(),
+      Msg::GameMode(mode) => format!("Game Mode: {}", mode),
+      Msg::GameModeTitle => "Game Mode".to_string(),
+      Msg::HighScores => "High Scores".to_string(),
+      Msg::Options => "Options".to_string(),
+      Msg::Quit => "Quit".to_string(),
+      Msg::NoSavedGame => "no saved game found".to_string(),
+      Msg::Mode(mode) => match mode {
+        0 => "classic",
+        1 => "real-time",
+        2 => "daily challenge",
+        3 => "co-op (2 players, 1 keyboard)",
+        _ => "puzzles",
+      }.to_string(),
+      Msg::OptionUnicode(on) => format!("Unicode theme: {}", on_off_en(*on)),
+      Msg::OptionSquare(on) => format!("Square cells: {}", on_off_en(*on)),
+      Msg::OptionAccessible(on) => format!("Accessible mode: {}", on_off_en(*on)),
+      Msg::OptionLanguage(lang) => format!("Language: {}", lang.name()),
+      Msg::OptionDifficulty(difficulty) => format!("Difficulty: {}", Msg::Difficulty(*difficulty).en()),
+      Msg::Difficulty(difficulty) => difficulty.name().to_string(),
+      Msg::Back => "Back".to_string(),
+      Msg::HighScoresTitle => "*** high scores ***".to_string(),
+      Msg::NoRecords => "no records yet".to_string(),
+      Msg::ScoreFileCorrupt => "the score file is corrupted".to_string(),
+      Msg::ScoreSaveFailed(e) => format!("failed to save the score: {}", e),
+      Msg::Paused => "paused".to_string(),
+      Msg::Resume => "resume".to_string(),
+      Msg::Help => "help".to_string(),
+      Msg::Statistics => "statistics".to_string(),
+      Msg::Achievements => "achievements".to_string(),
+      Msg::WatchFailed(addr, e) => format!("failed to watch {}: {}", addr, e),
+      Msg::Watching(addr) => format!("watching {} (q: quit)", addr),
+      Msg::GameEnded => "the game has ended".to_string(),
+      Msg::PuzzleTitle(idx, total, name) => format!("puzzle {}/{}: {}", idx, total, name),
+      Msg::Moves(turns, par) => format!("moves: {}, par: {}", turns, par),
+      Msg::TeleportDisabled => "teleport is disabled in puzzle mode".to_string(),
+      Msg::TeleportsLeft(left) => format!(", teleports: {}", left),
+      Msg::NoTeleportsLeft => "no teleports left on this level".to_string(),
+      Msg::UnsafeMove => "that move is not safe".to_string(),
+      Msg::Solved(turns, rating) => {
+        let rating = match rating {
+          Ordering::Less => "under par!",
+          Ordering::Equal => "par",
+          Ordering::Greater => "over par",
+        };
+        format!("solved in {} moves ({}). n: next, r: retry, q: quit", turns, rating)
+      },
+      Msg::PuzzleFailed(turns) => format!("failed after {} moves. r: retry, n: next, q: quit", turns),
+      Msg::AllPuzzlesPlayed => "you have played all puzzles".to_string(),
+      Msg::DailySaveFailed => "failed to save the daily ranking".to_string(),
+      Msg::AlreadyPlayedDaily(date) =>
+        format!("you have already played the daily board of {}. this attempt is not ranked", date),
+      Msg::DailyRanking(date) => format!("daily ranking {}", date),
+      Msg::YourScore(score, true) => format!("your score: {}", score),
+      Msg::YourScore(score, false) => format!("your score: {} (not ranked)", score),
+      Msg::CannotCreateField(level, e) =>
+        format!("cannot create the field for level {}: {}", level, Msg::FieldError(e).en()),
+      Msg::FieldError(e) => e.to_string(),
+      Msg::OpponentDown => "your opponent is down. you win the match!".to_string(),
+      Msg::SavePrompt => "save and quit? (y: save and quit, n: quit, other: cancel)".to_string(),
+      Msg::SaveFailed => "failed to save the game".to_string(),
+      Msg::PlayerDown(who) => format!("player {} is down", who + 1),
+      Msg::YouLoseToOpponent => "you lose. your opponent survived longer".to_string(),
+      Msg::OpponentWentDownFirst => "your opponent went down first. you win the match!".to_string(),
+      Msg::YouLose => "you lose".to_string(),
+      Msg::YouWin => "you win".to_string(),
+      Msg::StatisticsTitle => "*** statistics ***".to_string(),
+      Msg::Stat(stat) => match stat {
+        Stat::GamesPlayed => "games played",
+        Stat::RobotsDestroyed => "robots destroyed",
+        Stat::SuperRobotsDestroyed => "super robots destroyed",
+        Stat::Teleports => "teleports used",
+        Stat::DeathsByRobot => "deaths by robot",
+        Stat::DeathsByScrap => "deaths by scrap",
+        Stat::HighestLevel => "highest level",
+        Stat::LongestStopStreak => "longest stop streak",
+      }.to_string(),
+      Msg::AchievementUnlocked(titles) => format!("*** achievement unlocked: {} ***", titles),
+      Msg::AchievementsSaveFailed => "failed to save achievements".to_string(),
+      Msg::AchievementsTitle(profile) => format!("*** achievements ({}) ***", profile),
+      Msg::AchievementTitle(a) => a.title().to_string(),
+      Msg::AchievementDescription(a) => a.description().to_string(),
+      Msg::HelpTitle => "*** help ***".to_string(),
+      Msg::HelpExtraKeys => "p: pause, t: statistics, h: achievements, ?: this help".to_string(),
+      Msg::HelpMove => "move:".to_string(),
+      Msg::HelpStay => "stay".to_string(),
+      Msg::HelpTeleport => "teleport to a random cell".to_string(),
+      Msg::HelpStop => "stop (wait until the level ends)".to_string(),
+      Msg::HelpQuit => "quit".to_string(),
+      Msg::HelpLegend => "legend:".to_string(),
+      Msg::LegendPlayer => "player".to_string(),
+      Msg::LegendPartner => "player 2".to_string(),
+      Msg::LegendRobot => "robot".to_string(),
+      Msg::LegendSuperRobot => "super robot (moves twice)".to_string(),
+      Msg::LegendSmartRobot => "smart robot (avoids scrap and robots)".to_string(),
+      Msg::LegendScrap => "scrap".to_string(),
+      Msg::PlayerToMove(who, glyph) => format!("player {} ({}) to move", who + 1, glyph),
+      Msg::OpponentStatus(level, score) => format!("opponent level: {}, score: {}", level, score),
+      Msg::OpponentDisconnected => "opponent disconnected".to_string(),
+      Msg::Breakdown(turn) => format!("+{} (robots {}, combo {}, chain {}, no teleport {}, stop {})",
+        turn.total(), turn.base, turn.combo, turn.chain, turn.clean, turn.stop),
+      Msg::HighScore(score) => format!("HighScore!! {} points", score),
+      Msg::SummaryDown(who) => format!("player {} is down", who + 1),
+      Msg::SummaryPosition(who, x, y, robots, super_robots, scraps) =>
+        format!("player {} at column {}, row {}. {} robots, {} super robots, {} scraps",
+          who + 1, x, y, robots, super_robots, scraps),
+      Msg::NoRobotsLeft => "no robots left".to_string(),
+      Msg::Nearest(threats) => format!("nearest: {}", threats),
+      Msg::Threat(super_robot, distance, direction) =>
+        format!("{} {} {}", if *super_robot { "super robot" } else { "robot" }, distance, direction_en(*direction)),
+      Msg::Neighbour(direction, state) => {
+        let state = match state {
+          NeighbourState::Safe => "safe",
+          NeighbourState::Unsafe => "unsafe",
+          NeighbourState::Wall => "wall",
+          NeighbourState::Blocked => "blocked",
+        };
+        format!("{} {}", direction_en(*direction), state)
+      },
+    }
+  }
+
+  /// 日本語のメッセージを返します
+  fn ja(&self) -> String {
+    match self {
+      Msg::Status(level, score) => format!("レベル: {}, スコア: {}", level, score),
+      Msg::CliError(e) => format!("robots: {}", match e {
+        CliError::UnknownOption(option) => format!("{}というオプションはありません", option),
+        CliError::UnexpectedArgument(arg) => format!("余分な引数{}があります", arg),
+        CliError::MissingValue(option) => format!("{}には値が必要です", option),
+        CliError::InvalidValue(option, value) => format!("{}の値{}が正しくありません", option, value),
+        CliError::OutOfRange { option, min, max } => format!("{}は{}から{}の間で指定してください", option, min, max),
+        CliError::FixedInDaily(option) => format!("{}は--dailyと一緒に指定できません", option),
+        CliError::TooManyRobots { robots, cells } =>
+          format!("--max-robots {}はrobotを配置できる{}マスに収まりません", robots, cells),
+      }),
+      Msg::UsageHint => "使い方は`robots --help`で確認できます".to_string(),
+      Msg::Usage => [
+        "使い方: robots [オプション] [コマンド]",
+        "",
+        "コマンド:",
+        "  host [port]          対戦相手を待つ",
+        "  join [address]       addressの対戦相手と対戦する",
+        "  watch [address]      addressのゲームを観戦する",
+        "  coop                 2人で1つのfieldをプレイする",
+        "  puzzle [n]           n番のパズルから遊ぶ",
+        "  daily                今日のデイリーランキングを表示する",
+        "  stats                累計の記録を表示する",
+        "  achievements         実績の一覧を表示する",
+        "",
+        "オプション:",
+        "  --width <n>          fieldの横幅 (デフォルト 100)",
+        "  --height <n>         fieldの縦幅 (デフォルト 40)",
+        "  --robots <n>         levelごとに増えるrobotの数 (デフォルト 5)",
+        "  --max-robots <n>     robotの数の上限 (デフォルト 40)",
+        "  --difficulty <name>  難易度 easy, normal, hard, nightmare (デフォルト normal)",
+        "  --smart-robots       level 1からscrapを避けるsmart robotを配置する",
+        "  --min-distance <n>   開始時のplayerとrobotの最小の距離 (デフォルト 1)",
+        "  --start-level <n>    最初のlevel (デフォルト 1)",
+        "  --score-file <file>  ハイスコアを記録するファイル (デフォルト score.dat)",
+        "  --seed <n>           fieldの生成とランダム移動の乱数のシード",
+        "  --realtime [ms]      msミリ秒ごとにrobotを動かす",
+        "  --daily              今日のデイリーチャレンジを遊ぶ",
+        "  --resume             確認せずに中断したゲームを再開する",
+        "  --spectate [addr]    addrで観戦を受け付ける",
+        "  --profile <name>     実績を記録するプロフィール名 (空白は使えません)",
+        "  --unicode            Unicodeの記号でfieldを描画する",
+        "  --square             1マスを2列で描画する",
+        "  --accessible         毎ターンfieldの状況を文章で表示する",
+        "  --lang <en|ja>       表示する言語",
+        "  --ansi               ncursesの代わりにANSIエスケープシーケンスで描画する",
+        "  -h, --help           この使い方を表示する",
+      ].join("\n"),
+      Msg::TerminalInitFailed(e) => format!("端末を初期化できません: {}", e),
+      Msg::WaitingForOpponent(port) => format!("ポート{}で対戦相手を待っています...", port),
+      Msg::HostFailed(e) => format!("対戦の待ち受けに失敗しました: {}", e),
+      Msg::JoinFailed(addr, e) => format!("{}に接続できません: {}", addr, e),
+      Msg::SpectateReady(addr) => format!("{}で観戦できます", addr),
+      Msg::SpectateFailed(addr, e) => format!("{}で観戦を受け付けられません: {}", addr, e),
+      Msg::ResumePrompt => "中断したゲームがあります。再開しますか? (y/n)".to_string(),
+      Msg::LoadFailed(e) => {
+        let reason = match e {
+          SaveError::Io(kind) => format!("ファイルを読み込めません({})", kind),
+          SaveError::NotASave => "セーブファイルではありません".to_string(),
+          SaveError::UnsupportedVersion(version) => format!("形式{}はこのバージョンより新しいため読み込めません", version),
+          SaveError::Malformed(key) => format!("{}の行が無いか形式が違います", key),
+          SaveError::TooLarge { width, height } => format!("fieldの大きさ{}x{}は大きすぎます", width, height),
+          SaveError::OutOfBounds(p) => format!("({}, {})はfieldの外です", p.x, p.y),
+          SaveError::Overlap(p) => format!("({}, {})に複数のobjectがあります", p.x, p.y),
+        };
+        format!("中断したゲームを読み込めません: {}", reason)
+      },
+      Msg::DeleteSavePrompt => "壊れたセーブファイルを削除しますか? (y/n)".to_string(),
+      Msg::StatsSaveFailed(e) => format!("累計の記録を保存できません: {}", e),
+      Msg::PressAnyKey => "(何かキーを押してください)".to_string(),
+      Msg::PressAnyKeyToReturn => "何かキーを押すと戻ります".to_string(),
+      Msg::MenuHint => "i/,: 移動, スペース/Enter: 決定, q: 戻る".to_string(),
+      Msg::NewGame => "新しいゲーム".to_string(),
+      Msg::Continue => "続きから".to_string(),
+      Msg::GameMode(mode) => format!("遊び方: {}", mode),
+      Msg::GameModeTitle => "遊び方".to_string(),
+      Msg::HighScores => "ハイスコア".to_string(),
+      Msg::Options => "設定".to_string(),
+      Msg::Quit => "終了".to_string(),
+      Msg::NoSavedGame => "中断したゲームはありません".to_string(),
+      Msg::Mode(mode) => match mode {
+        0 => "通常",
+        1 => "リアルタイム",
+        2 => "デイリー",
+        3 => "協力プレイ(1つのキーボードで2人)",
+        _ => "パズル",
+      }.to_string(),
+      Msg::OptionUnicode(on) => format!("Unicodeの記号: {}", on_off_ja(*on)),
+      Msg::OptionSquare(on) => format!("正方形のマス: {}", on_off_ja(*on)),
+      Msg::OptionAccessible(on) => format!("アクセシブルモード: {}", on_off_ja(*on)),
+      Msg::OptionLanguage(lang) => format!("言語: {}", lang.name()),
+      Msg::OptionDifficulty(difficulty) => format!("難易度: {}", Msg::Difficulty(*difficulty).ja()),
+      Msg::Difficulty(difficulty) => match difficulty {
+        Difficulty::Easy => "やさしい",
+        Difficulty::Normal => "ふつう",
+        Difficulty::Hard => "むずかしい",
+        Difficulty::Nightmare => "悪夢",
+      }.to_string(),
+      Msg::Back => "戻る".to_string(),
+      Msg::HighScoresTitle => "*** ハイスコア ***".to_string(),
+      Msg::NoRecords => "まだ記録がありません".to_string(),
+      Msg::ScoreFileCorrupt => "スコアファイルの形式が違います".to_string(),
+      Msg::ScoreSaveFailed(e) => format!("スコアを保存できません: {}", e),
+      Msg::Paused => "一時停止".to_string(),
+      Msg::Resume => "ゲームに戻る".to_string(),
+      Msg::Help => "操作方法".to_string(),
+      Msg::Statistics => "累計の記録".to_string(),
+      Msg::Achievements => "実績".to_string(),
+      Msg::WatchFailed(addr, e) => format!("{}を観戦できません: {}", addr, e),
+      Msg::Watching(addr) => format!("{}を観戦中 (q: 終了)", addr),
+      Msg::GameEnded => "ゲームが終了しました".to_string(),
+      Msg::PuzzleTitle(idx, total, name) => format!("パズル {}/{}: {}", idx, total, name),
+      Msg::Moves(turns, par) => format!("ターン数: {}, 目標: {}", turns, par),
+      Msg::TeleportDisabled => "パズルモードではランダム移動は使えません".to_string(),
+      Msg::TeleportsLeft(left) => format!(", ランダム移動: 残り{}回", left),
+      Msg::NoTeleportsLeft => "このレベルではもうランダム移動は使えません".to_string(),
+      Msg::UnsafeMove => "そこに移動するとrobotに倒されます".to_string(),
+      Msg::Solved(turns, rating) => {
+        let rating = match rating {
+          Ordering::Less => "目標より少ない!",
+          Ordering::Equal => "目標どおり",
+          Ordering::Greater => "目標より多い",
+        };
+        format!("{}ターンで解けました({})。n: 次へ, r: やり直す, q: 終了", turns, rating)
+      },
+      Msg::PuzzleFailed(turns) => format!("{}ターンで失敗しました。r: やり直す, n: 次へ, q: 終了", turns),
+      Msg::AllPuzzlesPlayed => "全てのパズルを遊びました".to_string(),
+      Msg::DailySaveFailed => "デイリーモードのランキングを保存できません".to_string(),
+      Msg::AlreadyPlayedDaily(date) =>
+        format!("{}の盤面は既に遊んでいます。今回の記録はランキングの対象になりません", date),
+      Msg::DailyRanking(date) => format!("デイリーランキング {}", date),
+      Msg::YourScore(score, true) => format!("あなたのスコア: {}", score),
+      Msg::YourScore(score, false) => format!("あなたのスコア: {} (ランキング対象外)", score),
+      Msg::CannotCreateField(level, e) =>
+        format!("レベル{}のfieldを生成できません: {}", level, Msg::FieldError(e).ja()),
+      Msg::FieldError(e) => match e {
+        FieldError::EmptyField { width, height } => format!("fieldの大きさ{}x{}は空です", width, height),
+        FieldError::TooManyRobots { robots, free_cells } =>
+          format!("{}体のrobotはfieldの空きマス{}個に収まりません", robots, free_cells),
+        FieldError::OutOfBounds(p) => format!("({}, {})はfieldの外です", p.x, p.y),
+        FieldError::Occupied(p, obj) => {
+          let name = match obj {
+            Object::Partner => "2人目のplayer",
+            obj => obj.name(),
+          };
+          format!("({}, {})には{}があります", p.x, p.y, name)
+        },
+        FieldError::NoPlayer(who) => format!("player {}はfieldにいません", who + 1),
+      },
+      Msg::OpponentDown => "対戦相手が負けました。あなたの勝ちです!".to_string(),
+      Msg::SavePrompt => "保存して終了しますか? (y: 保存して終了, n: 終了, その他: キャンセル)".to_string(),
+      Msg::SaveFailed => "ゲームを保存できません".to_string(),
+      Msg::PlayerDown(who) => format!("player {}が倒されました", who + 1),
+      Msg::YouLoseToOpponent => "あなたの負けです。対戦相手の方が長く生き残りました".to_string(),
+      Msg::OpponentWentDownFirst => "対戦相手が先に負けていました。あなたの勝ちです!".to_string(),
+      Msg::YouLose => "あなたの負けです".to_string(),
+      Msg::YouWin => "クリア!".to_string(),
+      Msg::StatisticsTitle => "*** 累計の記録 ***".to_string(),
+      Msg::Stat(stat) => match stat {
+        Stat::GamesPlayed => "プレイしたゲーム",
+        Stat::RobotsDestroyed => "倒したrobot",
+        Stat::SuperRobotsDestroyed => "倒したsuper robot",
+        Stat::Teleports => "ランダム移動の回数",
+        Stat::DeathsByRobot => "robotに倒された回数",
+        Stat::DeathsByScrap => "scrapに倒された回数",
+        Stat::HighestLevel => "最高レベル",
+        Stat::LongestStopStreak => "止まるモードの最長ターン",
+      }.to_string(),
+      Msg::AchievementUnlocked(titles) => format!("*** 実績を獲得: {} ***", titles),
+      Msg::AchievementsSaveFailed => "実績を保存できません".to_string(),
+      Msg::AchievementsTitle(profile) => format!("*** 実績 ({}) ***", profile),
+      Msg::AchievementTitle(a) => match a {
+        Achievement::FirstScrap => "はじめてのスクラップ",
+        Achievement::SuperScrap => "大物狩り",
+        Achievement::NoTeleport => "地に足をつけて",
+        Achievement::TenInOneTurn => "スクラップ置き場",
+        Achievement::StopWin => "不動の構え",
+        Achievement::Level10 => "ベテラン",
+      }.to_string(),
+      Msg::AchievementDescription(a) => match a {
+        Achievement::FirstScrap => "robotを倒す",
+        Achievement::SuperScrap => "super robotを倒す",
+        Achievement::NoTeleport => "ランダム移動を使わずにレベルをクリアする",
+        Achievement::TenInOneTurn => "1ターンで10体のrobotを倒す",
+        Achievement::StopWin => "止まるモードでレベルをクリアする",
+        Achievement::Level10 => "レベル10に到達する",
+      }.to_string(),
+      Msg::HelpTitle => "*** 操作方法 ***".to_string(),
+      Msg::HelpExtraKeys => "p: 一時停止, t: 累計の記録, h: 実績, ?: この画面".to_string(),
+      Msg::HelpMove => "移動:".to_string(),
+      Msg::HelpStay => "移動しない".to_string(),
+      Msg::HelpTeleport => "ランダムなマスに移動".to_string(),
+      Msg::HelpStop => "止まる(レベルが終わるまで動かない)".to_string(),
+      Msg::HelpQuit => "終了".to_string(),
+      Msg::HelpLegend => "記号:".to_string(),
+      Msg::LegendPlayer => "player".to_string(),
+      Msg::LegendPartner => "2人目のplayer".to_string(),
+      Msg::LegendRobot => "robot".to_string(),
+      Msg::LegendSuperRobot => "super robot (2マス動く)".to_string(),
+      Msg::LegendSmartRobot => "smart robot (scrapと他のrobotを避ける)".to_string(),
+      Msg::LegendScrap => "scrap".to_string(),
+      Msg::PlayerToMove(who, glyph) => format!("player {} ({}) の番です", who + 1, glyph),
+      Msg::OpponentStatus(level, score) => format!("相手のレベル: {}, スコア: {}", level, score),
+      Msg::OpponentDisconnected => "対戦相手との接続が切れました".to_string(),
+      Msg::Breakdown(turn) => format!("+{} (robot {}, 同時 {}, 連続 {}, ランダム移動なし {}, 止まる {})",
+        turn.total(), turn.base, turn.combo, turn.chain, turn.clean, turn.stop),
+      Msg::HighScore(score) => format!("ハイスコア!! {}点", score),
+      Msg::SummaryDown(who) => format!("player {}は倒されました", who + 1),
+      Msg::SummaryPosition(who, x, y, robots, super_robots, scraps) =>
+        format!("player {}は{}列{}行。robot {}体、super robot {}体、scrap {}個",
+          who + 1, x, y, robots, super_robots, scraps),
+      Msg::NoRobotsLeft => "robotは残っていません".to_string(),
+      Msg::Nearest(threats) => format!("近いrobot: {}", threats),
+      Msg::Threat(super_robot, distance, direction) =>
+        format!("{} {}に{}マス", if *super_robot { "super robot" } else { "robot" }, direction_ja(*direction), distance),
+      Msg::Neighbour(direction, state) => {
+        let state = match state {
+          NeighbourState::Safe => "安全",
+          NeighbourState::Unsafe => "危険",
+          NeighbourState::Wall => "壁",
+          NeighbourState::Blocked => "移動不可",
+        };
+        format!("{} {}", direction_ja(*direction), state)
+      },
+    }
+  }
+}
+
+/// 英語でオンかオフかを返します
+/// * `on` - オンか
+fn on_off_en(on: bool) -> &'static str {
+  if on { "on" } else { "off" }
+}
+
+/// 日本語でオンかオフかを返します
+/// * `on` - オンか
+fn on_off_ja(on: bool) -> &'static str {
+  if on { "オン" } else { "オフ" }
+}
+
+/// 英語で方向の名前を返します
+/// * `direction` - 方向
+fn direction_en(direction: Direction) -> &'static str {
+  match direction {
+    Direction::North => "north",
+    Direction::NorthEast => "north-east",
+    Direction::East => "east",
+    Direction::SouthEast => "south-east",
+    Direction::South => "south",
+    Direction::SouthWest => "south-west",
+    Direction::West => "west",
+    Direction::NorthWest => "north-west",
+    Direction::Stay => "stay",
+    Direction::Here => "here",
+  }
+}
+
+/// 日本語で方向の名前を返します
+/// * `direction` - 方向
+fn direction_ja(direction: Direction) -> &'static str {
+  match direction {
+    Direction::North => "北",
+    Direction::NorthEast => "北東",
+    Direction::East => "東",
+    Direction::SouthEast => "南東",
+    Direction::South => "南",
+    Direction::SouthWest => "南西",
+    Direction::West => "西",
+    Direction::NorthWest => "北西",
+    Direction::Stay => "その場",
+    Direction::Here => "ここ",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::stats::Stats;
+
+#[test]
+  fn catalog_test() {
+    assert_eq!(Msg::YouLose.text(Lang::En), "you lose");
+    assert_eq!(Msg::YouLose.text(Lang::Ja), "あなたの負けです");
+    assert_eq!(Msg::Threat(false, 2, Direction::NorthWest).text(Lang::En), "robot 2 north-west");
+    assert_eq!(Msg::Threat(false, 2, Direction::NorthWest).text(Lang::Ja), "robot 北西に2マス");
+    assert_eq!(Msg::Neighbour(Direction::Stay, NeighbourState::Unsafe).text(Lang::Ja), "その場 危険");
+    // 累計の記録と実績は全て翻訳されている
+    for (stat, _) in Stats::default().rows() {
+      assert_ne!(Msg::Stat(stat).text(Lang::Ja), Msg::Stat(stat).text(Lang::En));
+    }
+    for a in Achievement::ALL.iter() {
+      assert_ne!(Msg::AchievementTitle(*a).text(Lang::Ja), a.title());
+    }
+    let occupied = FieldError::Occupied(crate::internal::Point::new(1, 2), Object::SuperRobot);
+    assert_eq!(Msg::FieldError(&occupied).text(Lang::En), "(1, 2) is occupied by super robot");
+    assert_eq!(Msg::FieldError(&occupied).text(Lang::Ja), "(1, 2)にはsuper robotがあります");
+    assert_eq!(Lang::parse("ja"), Some(Lang::Ja));
+    assert_eq!(Lang::parse("fr"), None);
+  }
+
+#[test]
+  fn pad_test() {
+    assert_eq!(width("robot"), 5);
+    assert_eq!(width("倒したrobot"), 11);
+    assert_eq!(pad("実績", 6), "実績  ");
+  }
+}
@@ -1,6 +1,8 @@
 use rand::Rng;
-use ncurses::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use crate::term::{Renderer, ERR};
+use crate::theme::Theme;
+use crate::viewport::Viewport;
 
 /// プログラムを終了
 pub const KEY_QUIT:  i32 = b'q' as i32;
@@ -26,6 +28,46 @@ pub const KEY_LDOWN: i32 = b'm' as i32;
 pub const KEY_RAND:  i32 = b'k' as i32;
 /// これ以降は動かない
 pub const KEY_STOP:  i32 = b'0' as i32;
+/// 確認に同意する
+pub const KEY_YES:   i32 = b'y' as i32;
+/// 確認を拒否する
+pub const KEY_NO:    i32 = b'n' as i32;
+
+/// 入力されたキーと操作の対応を表します
+/// 操作は1人目のplayerのキー(`KEY_*`)で表します
+pub struct KeyMap {
+  /// (入力されたキー, 対応する操作)のリスト
+  pub keys: Vec<(i32, i32)>,
+}
+
+impl KeyMap {
+  /// 1人目のplayerのキー配置を返します
+  pub fn player1() -> KeyMap {
+    let keys = [KEY_QUIT, KEY_LEFT, KEY_DOWN, KEY_UP, KEY_RIGHT, KEY_STAY,
+      KEY_RUP, KEY_RDOWN, KEY_LUP, KEY_LDOWN, KEY_RAND, KEY_STOP];
+    KeyMap { keys: keys.iter().map(|k| (*k, *k)).collect() }
+  }
+
+  /// 協力プレイでの2人目のplayerのキー配置を返します
+  pub fn player2() -> KeyMap {
+    KeyMap {
+      keys: vec![
+        (KEY_QUIT, KEY_QUIT),
+        (b'w' as i32, KEY_LUP),   (b'e' as i32, KEY_UP),   (b'r' as i32, KEY_RUP),
+        (b's' as i32, KEY_LEFT),  (b'd' as i32, KEY_RAND), (b'f' as i32, KEY_RIGHT),
+        (b'x' as i32, KEY_LDOWN), (b'c' as i32, KEY_DOWN), (b'v' as i32, KEY_RDOWN),
+        (b'a' as i32, KEY_STAY),  (b'1' as i32, KEY_STOP),
+      ],
+    }
+  }
+
+  /// 入力されたキーを対応する操作に変換します
+  /// 対応する操作が無い場合は`ERR`を返します
+  /// * `ch` - 入力されたキー
+  pub fn translate(&self, ch: i32) -> i32 {
+    self.keys.iter().find(|(key, _)| *key == ch).map_or(ERR, |(_, action)| *action)
+  }
+}
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 /// フィールド上の位置を示します
@@ -42,21 +84,53 @@ impl Point {
   }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 /// フィールド上のオブジェクトの種類を表します
 pub enum Object {
   /// プレイヤー
   Player,
+  /// 協力プレイでの2人目のプレイヤー
+  Partner,
   /// ロボット
   Robot,
   /// スーパーロボ
   SuperRobot,
+  /// scrapや他のrobotを避けて進むロボット
+  SmartRobot,
   /// スクラップ
   Scrap,
   /// 何もない
   Null,
 }
 
+impl Object {
+  /// 画面に表示する文字を返します
+  pub fn glyph(&self) -> char {
+    match self {
+      Object::Player => '@',
+      Object::Partner => '&',
+      Object::Robot => '+',
+      Object::Scrap => '*',
+      Object::SuperRobot => '$',
+      Object::SmartRobot => '%',
+      Object::Null => ' ',
+    }
+  }
+
+  /// メッセージに使う名前を返します
+  pub fn name(&self) -> &'static str {
+    match self {
+      Object::Player => "player",
+      Object::Partner => "player 2",
+      Object::Robot => "robot",
+      Object::Scrap => "scrap",
+      Object::SuperRobot => "super robot",
+      Object::SmartRobot => "smart robot",
+      Object::Null => "nothing",
+    }
+  }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 /// 次に行う処理の種類を表します
 pub enum Status {
@@ -70,7 +144,95 @@ pub enum Status {
   Exit,
 }
 
+/// 直前のrobotの移動で起きたことを表します
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TurnReport {
+  /// 倒したrobotの数
+  pub robots: usize,
+  /// 倒したsuper_robotの数
+  pub super_robots: usize,
+  /// playerを倒したobject
+  pub killed_by: Option<Object>,
+}
+
+/// fieldの生成やplayerの移動に失敗した理由を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FieldError {
+  /// fieldの横幅か縦幅が0
+  EmptyField {
+    /// 指定された横幅
+    width: usize,
+    /// 指定された縦幅
+    height: usize,
+  },
+  /// robotを配置する空きが足りない
+  TooManyRobots {
+    /// 配置しようとしたrobotの数
+    robots: usize,
+    /// robotを配置できるマスの数
+    free_cells: usize,
+  },
+  /// 座標がfieldの外
+  OutOfBounds(Point),
+  /// 移動先に他のobjectがある
+  Occupied(Point, Object),
+  /// 指定の番号のplayerがいないか、既に負けている
+  NoPlayer(usize),
+}
+
+impl std::fmt::Display for FieldError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      FieldError::EmptyField { width, height } =>
+        write!(f, "the field size {}x{} is empty", width, height),
+      FieldError::TooManyRobots { robots, free_cells } =>
+        write!(f, "{} robots do not fit in the {} free cells of the field", robots, free_cells),
+      FieldError::OutOfBounds(p) => write!(f, "({}, {}) is outside the field", p.x, p.y),
+      FieldError::Occupied(p, obj) => write!(f, "({}, {}) is occupied by {}", p.x, p.y, obj.name()),
+      FieldError::NoPlayer(who) => write!(f, "player {} is not on the field", who + 1),
+    }
+  }
+}
+
+impl std::error::Error for FieldError {}
+
+/// robotを配置するときの条件を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Placement {
+  /// robotの何体に1体をsuper_robotにするか(0の場合はsuper_robotを配置しない)
+  pub super_robot_ratio: usize,
+  /// super_robot以外のrobotの何体に1体をsmart_robotにするか(0の場合はsmart_robotを配置しない)
+  pub smart_robot_ratio: usize,
+  /// robotとplayerの初期位置の最小の距離(斜めも1歩と数える、1以下の場合はplayerの隣にも配置する)
+  pub min_distance: usize,
+}
+
+impl Default for Placement {
+  fn default() -> Placement {
+    Placement { super_robot_ratio: 10, smart_robot_ratio: 0, min_distance: 1 }
+  }
+}
+
+/// 1回の移動でのrobotの動きを表します
+struct Move {
+  /// robotの種類
+  kind: Object,
+  /// 移動前の位置
+  from: Point,
+  /// 移動先
+  to: Point,
+}
+
+impl Move {
+  /// 移動の中点を2倍した座標を返します
+  /// 入れ替わったrobotや斜めに交差したrobotは同じ値になります
+  fn midpoint(&self) -> Point {
+    Point::new(self.from.x + self.to.x, self.from.y + self.to.y)
+  }
+}
+
 /// ゲームフィールドを表します
+#[derive(Clone, Debug)]
 pub struct Field {
   /// fieldが配置される場所
   pub pos: Point,
@@ -80,140 +242,534 @@ pub struct Field {
   pub height: usize,
   /// playerの位置
   pub player_pos: Point,
+  /// playerが生き残っているか
+  pub player_alive: bool,
+  /// 協力プレイでの2人目のplayerの位置
+  pub partner_pos: Option<Point>,
+  /// 2人目のplayerが生き残っているか
+  pub partner_alive: bool,
   /// robotの位置のリスト
   pub robots_pos: Vec<Point>,
   /// super_robotの位置のリスト
   pub super_robots_pos: Vec<Point>,
+  /// smart_robotの位置のリスト
+  pub smart_robots_pos: Vec<Point>,
   /// scrapの位置のリスト
   pub scraps_pos: HashSet<Point>,
   /// fieldを表すリスト
   pub field: Vec<Vec<Object>>,
+  /// 直前のrobotの移動で起きたこと
+  pub report: TurnReport,
 }
 
 impl Field {
   /// fieldを生成し、robotをランダムに配置します
+  /// 生成できない大きさの場合はpanicします
   /// * `pos` - fieldが配置される場所
   /// * `width` - fieldの横幅
   /// * `height` - fieldの縦幅
   /// * `robots_num` - robotの数
+  #[cfg(test)]
   pub fn new(pos: Point, width: usize, height: usize, robots_num: usize) -> Field {
-    let mut rng = rand::thread_rng();
+    Field::try_new(pos, width, height, robots_num).expect("fieldを生成できません")
+  }
+
+  /// fieldを生成し、robotをランダムに配置します
+  /// fieldが空の場合やrobotを配置しきれない場合はエラーを返します
+  /// * `pos` - fieldが配置される場所
+  /// * `width` - fieldの横幅
+  /// * `height` - fieldの縦幅
+  /// * `robots_num` - robotの数
+  #[cfg(test)]
+  pub fn try_new(pos: Point, width: usize, height: usize, robots_num: usize) -> Result<Field, FieldError> {
+    Field::try_with_placement(pos, width, height, robots_num, &mut rand::thread_rng(), false, &Placement::default())
+  }
+
+  /// 指定の乱数生成器を使ってfieldを生成し、robotをランダムに配置します
+  /// 生成できない大きさの場合はpanicします
+  /// * `pos` - fieldが配置される場所
+  /// * `width` - fieldの横幅
+  /// * `height` - fieldの縦幅
+  /// * `robots_num` - robotの数
+  /// * `rng` - robotの配置に使う乱数生成器
+  #[cfg(test)]
+  pub fn with_rng<R: Rng>(pos: Point, width: usize, height: usize, robots_num: usize, rng: &mut R) -> Field {
+    Field::try_with_placement(pos, width, height, robots_num, rng, false, &Placement::default()).expect("fieldを生成できません")
+  }
+
+  /// 協力プレイ用に2人のplayerを配置したfieldを生成し、robotをランダムに配置します
+  /// 生成できない大きさの場合はpanicします
+  /// * `pos` - fieldが配置される場所
+  /// * `width` - fieldの横幅
+  /// * `height` - fieldの縦幅
+  /// * `robots_num` - robotの数
+  /// * `rng` - robotの配置に使う乱数生成器
+  #[cfg(test)]
+  pub fn with_partner<R: Rng>(pos: Point, width: usize, height: usize, robots_num: usize, rng: &mut R) -> Field {
+    Field::try_with_partner(pos, width, height, robots_num, rng).expect("fieldを生成できません")
+  }
+
+  /// 協力プレイ用に2人のplayerを配置したfieldを生成し、robotをランダムに配置します
+  /// 2人を並べられない場合やrobotを配置しきれない場合はエラーを返します
+  /// * `pos` - fieldが配置される場所
+  /// * `width` - fieldの横幅
+  /// * `height` - fieldの縦幅
+  /// * `robots_num` - robotの数
+  /// * `rng` - robotの配置に使う乱数生成器
+  #[cfg(test)]
+  pub fn try_with_partner<R: Rng>(pos: Point, width: usize, height: usize, robots_num: usize, rng: &mut R) -> Result<Field, FieldError> {
+    Field::try_with_placement(pos, width, height, robots_num, rng, true, &Placement::default())
+  }
+
+  /// 配置の条件を指定してfieldを生成し、robotをランダムに配置します
+  /// fieldが空の場合やrobotを配置しきれない場合はエラーを返します
+  /// * `pos` - fieldが配置される場所
+  /// * `width` - fieldの横幅
+  /// * `height` - fieldの縦幅
+  /// * `robots_num` - robotの数
+  /// * `rng` - robotの配置に使う乱数生成器
+  /// * `partner` - 2人目のplayerを配置するか
+  /// * `placement` - robotを配置するときの条件
+  pub fn try_with_placement<R: Rng>(pos: Point, width: usize, height: usize, robots_num: usize, rng: &mut R,
+    partner: bool, placement: &Placement) -> Result<Field, FieldError> {
+    if width == 0 || height == 0 || (partner && width < 2) {
+      return Err(FieldError::EmptyField { width, height });
+    }
+    let (player, partner) = start_positions(width, height, partner);
+    let players: Vec<Point> = std::iter::once(player).chain(partner).collect();
+    let min_distance = std::cmp::max(placement.min_distance, 1);
+    let allowed = |p: Point| players.iter().all(|q| distance(p, *q) >= min_distance);
+    let free_cells = Field::free_cells(width, height, partner.is_some(), min_distance);
+    if robots_num > free_cells {
+      return Err(FieldError::TooManyRobots { robots: robots_num, free_cells });
+    }
     let mut field = vec![vec![Object::Null; width]; height];
-    let mut super_robots_pos: Vec<Point> = vec![Point::new(0, 0); robots_num/10];
-    let mut robots = vec![Point::new(0, 0); robots_num-(robots_num/10)];
+    let super_robots_num = robots_num.checked_div(placement.super_robot_ratio).unwrap_or(0);
+    let smart_robots_num = (robots_num - super_robots_num).checked_div(placement.smart_robot_ratio).unwrap_or(0);
     let scraps: HashSet<Point> = HashSet::new();
-    let player = Point::new(width>>1, height>>1);
-
-    let mut player_idx = 0;
-    let mut coord_list = vec![Point::new(0, 0); width*height];
-    for y in 0..height {
-      for x in 0..width {
-        coord_list[y*width + x] = Point::new(x, y);
-        if Point::new(x, y) == player {
-          player_idx = y*width + x;
-        }
-      }
-    }
-    coord_list.remove(player_idx);
 
-    for i in 0..robots_num {
-      let idx = rng.gen::<usize>() % coord_list.len();
-      if i < robots.len() {
-        robots[i] = coord_list[idx];
-      } else {
-        super_robots_pos[i-robots.len()] = coord_list[idx];
-      }
-      coord_list.remove(idx);
-    }
+    // 選んだ順にrobot、smart_robot、super_robotにする
+    let mut robots = random_cells(width, height, robots_num, free_cells, allowed, rng);
+    let super_robots_pos = robots.split_off(robots_num - super_robots_num);
+    let smart_robots_pos = robots.split_off(robots.len() - smart_robots_num);
     for robot in &robots {
       field[robot.y][robot.x] = Object::Robot;
     }
     for sup_rob in &super_robots_pos {
       field[sup_rob.y][sup_rob.x] = Object::SuperRobot;
     }
+    for smart_rob in &smart_robots_pos {
+      field[smart_rob.y][smart_rob.x] = Object::SmartRobot;
+    }
     field[player.y][player.x] = Object::Player;
+    if let Some(partner) = partner {
+      field[partner.y][partner.x] = Object::Partner;
+    }
 
-    Field {
+    Ok(Field {
       pos,
       width,
       height,
-      player_pos: Point::new(width>>1, height>>1),
+      player_pos: player,
+      player_alive: true,
+      partner_pos: partner,
+      partner_alive: partner.is_some(),
       robots_pos: robots,
       super_robots_pos,
+      smart_robots_pos,
       scraps_pos: scraps,
       field,
+      report: TurnReport::default(),
+    })
+  }
+
+  /// robotを配置できるマスの数を返します
+  /// * `width` - fieldの横幅
+  /// * `height` - fieldの縦幅
+  /// * `partner` - 2人目のplayerを配置するか
+  /// * `min_distance` - robotとplayerの初期位置の最小の距離
+  pub fn free_cells(width: usize, height: usize, partner: bool, min_distance: usize) -> usize {
+    if width == 0 || height == 0 || (partner && width < 2) {
+      return 0;
     }
+    let (player, partner) = start_positions(width, height, partner);
+    let players: Vec<Point> = std::iter::once(player).chain(partner).collect();
+    let min_distance = std::cmp::max(min_distance, 1);
+    // 置けないマスはplayerを囲む正方形の中にしかないので、その範囲だけを数える
+    let reach = min_distance - 1;
+    let xs = players.iter().map(|p| p.x.saturating_sub(reach)).min().unwrap_or(0)
+      ..=players.iter().map(|p| std::cmp::min(p.x + reach, width - 1)).max().unwrap_or(0);
+    let ys = player.y.saturating_sub(reach)..=std::cmp::min(player.y + reach, height - 1);
+    let blocked = ys.flat_map(|y| xs.clone().map(move |x| Point::new(x, y)))
+      .filter(|p| players.iter().any(|q| distance(*p, *q) < min_distance))
+      .count();
+    width * height - blocked
   }
 
-  /// playerを移動させます
+  /// 各objectの位置からfieldを生成します
+  /// * `pos` - fieldが配置される場所
+  /// * `width` - fieldの横幅
+  /// * `height` - fieldの縦幅
+  /// * `player_pos` - playerの位置
+  /// * `robots_pos` - robotの位置のリスト
+  /// * `super_robots_pos` - super_robotの位置のリスト
+  /// * `scraps_pos` - scrapの位置のリスト
+  pub fn from_parts(pos: Point, width: usize, height: usize, player_pos: Point,
+    robots_pos: Vec<Point>, super_robots_pos: Vec<Point>, scraps_pos: HashSet<Point>) -> Field {
+    let mut field = Field {
+      pos,
+      width,
+      height,
+      player_pos,
+      player_alive: true,
+      partner_pos: None,
+      partner_alive: false,
+      robots_pos,
+      super_robots_pos,
+      smart_robots_pos: Vec::new(),
+      scraps_pos,
+      field: vec![vec![Object::Null; width]; height],
+      report: TurnReport::default(),
+    };
+    field.field_update();
+    field
+  }
+
+  /// 盤面を表す文字列の行からfieldを生成します
+  /// `.`または空白が空き、`@`がplayer、`&`が2人目のplayer、`+`がrobot、`$`がsuper_robot、`%`がsmart_robot、`*`がscrapです
+  /// 行の長さが揃っていない場合やplayerがちょうど1人でない場合は`None`を返します
+  /// * `rows` - 盤面の各行
+  /// * `pos` - fieldが配置される場所
+  pub fn from_rows(rows: &[&str], pos: Point) -> Option<Field> {
+    let width = rows.first()?.chars().count();
+    if width == 0 || rows.iter().any(|row| row.chars().count() != width) {
+      return None;
+    }
+    let mut players = Vec::new();
+    let mut partners = Vec::new();
+    let mut robots = Vec::new();
+    let mut super_robots = Vec::new();
+    let mut smart_robots = Vec::new();
+    let mut scraps = HashSet::new();
+    for (y, row) in rows.iter().enumerate() {
+      for (x, c) in row.chars().enumerate() {
+        let p = Point::new(x, y);
+        match c {
+          '.' | ' ' => (),
+          '@' => players.push(p),
+          '&' => partners.push(p),
+          '+' => robots.push(p),
+          '$' => super_robots.push(p),
+          '%' => smart_robots.push(p),
+          '*' => { scraps.insert(p); },
+          _ => return None,
+        }
+      }
+    }
+    if players.len() != 1 || partners.len() > 1 {
+      return None;
+    }
+
+    let mut field = Field::from_parts(pos, width, rows.len(), players[0], robots, super_robots, scraps);
+    field.smart_robots_pos = smart_robots;
+    if let Some(partner) = partners.first() {
+      field.partner_pos = Some(*partner);
+      field.partner_alive = true;
+    }
+    field.field_update();
+    Some(field)
+  }
+
+  /// `to_ascii`で作った文字列からfieldを生成します
+  /// 枠の有無は自動で判定し、各行の前後の空白と空行は無視します
+  /// 形式が違う場合は`None`を返します
+  /// * `s` - fieldを表す文字列
+  /// * `pos` - fieldが配置される場所
+  #[cfg(test)]
+  pub fn from_ascii(s: &str, pos: Point) -> Option<Field> {
+    let lines: Vec<&str> = s.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+    let framed = lines.len() >= 2
+      && [lines[0], lines[lines.len()-1]].iter().all(|l| l.chars().all(|c| c == '-'))
+      && lines[1..lines.len()-1].iter().all(|l| l.len() >= 2 && l.starts_with('|') && l.ends_with('|'));
+    if framed {
+      let rows: Vec<&str> = lines[1..lines.len()-1].iter().map(|l| &l[1..l.len()-1]).collect();
+      Field::from_rows(&rows, pos)
+    } else {
+      Field::from_rows(&lines, pos)
+    }
+  }
+
+  /// fieldを文字で表した文字列を返します
+  /// objectは画面と同じ文字で表し、空きは`.`で表します
+  /// 負けたplayerは表示しません
+  /// * `frame` - 周りに枠を付けるか
+  pub fn to_ascii(&self, frame: bool) -> String {
+    let border = "-".repeat(self.width + 2);
+    let mut s = String::new();
+    if frame {
+      s += &border;
+      s.push('\n');
+    }
+    for row in &self.field {
+      let line: String = row.iter().map(|obj| match obj {
+        Object::Null => '.',
+        obj => obj.glyph(),
+      }).collect();
+      if frame {
+        s += &format!("|{}|\n", line);
+      } else {
+        s += &line;
+        s.push('\n');
+      }
+    }
+    if frame {
+      s += &border;
+      s.push('\n');
+    }
+    s
+  }
+
+  /// 全ての種類のrobotを倒したかを返します
+  pub fn all_robots_destroyed(&self) -> bool {
+    self.robots_pos.is_empty() && self.super_robots_pos.is_empty() && self.smart_robots_pos.is_empty()
+  }
+
+  /// 生き残っているplayerの位置のリストを返します
+  pub fn living_players(&self) -> Vec<Point> {
+    let mut players = Vec::new();
+    if self.player_alive {
+      players.push(self.player_pos);
+    }
+    if let (Some(partner), true) = (self.partner_pos, self.partner_alive) {
+      players.push(partner);
+    }
+    players
+  }
+
+  /// 指定の番号のplayerが生き残っていればその位置を返します
+  /// * `who` - playerの番号(0: 1人目, 1: 2人目)
+  pub fn player(&self, who: usize) -> Option<Point> {
+    match who {
+      0 if self.player_alive => Some(self.player_pos),
+      1 if self.partner_alive => self.partner_pos,
+      _ => None,
+    }
+  }
+
+  /// 指定の番号のplayerを移動させます
   /// 指定の座標に移動できないときは`false`を返します
+  /// * `who` - playerの番号(0: 1人目, 1: 2人目)
   /// * `pos` - 移動先の座標
-  pub fn player_move(&mut self, pos: Point) -> bool {
-    let res;
+  pub fn move_player(&mut self, who: usize, pos: Point) -> bool {
+    self.try_move_player(who, pos).is_ok()
+  }
+
+  /// 指定の番号のplayerを移動させます
+  /// 指定の座標に移動できないときは理由をエラーで返します
+  /// * `who` - playerの番号(0: 1人目, 1: 2人目)
+  /// * `pos` - 移動先の座標
+  pub fn try_move_player(&mut self, who: usize, pos: Point) -> Result<(), FieldError> {
+    let (current, own) = self.check_move(who, pos)?;
+    self.field[current.y][current.x] = Object::Null;
+    self.field[pos.y][pos.x] = own;
+    match who {
+      0 => { self.player_pos = pos; },
+      _ => { self.partner_pos = Some(pos); },
+    }
+    Ok(())
+  }
+
+  /// 指定の番号のplayerが指定の座標に移動できるかを返します
+  /// * `who` - playerの番号(0: 1人目, 1: 2人目)
+  /// * `pos` - 移動先の座標
+  pub fn can_move_player(&self, who: usize, pos: Point) -> bool {
+    self.check_move(who, pos).is_ok()
+  }
+
+  /// playerが指定の座標に移動できるかを調べ、移動できる場合は現在の座標とplayerを表すobjectを返します
+  /// * `who` - playerの番号(0: 1人目, 1: 2人目)
+  /// * `pos` - 移動先の座標
+  fn check_move(&self, who: usize, pos: Point) -> Result<(Point, Object), FieldError> {
+    if pos.x >= self.width || pos.y >= self.height {
+      return Err(FieldError::OutOfBounds(pos));
+    }
+    let (current, own) = match who {
+      0 => (self.player_pos, Object::Player),
+      1 => (self.partner_pos.ok_or(FieldError::NoPlayer(1))?, Object::Partner),
+      _ => return Err(FieldError::NoPlayer(who)),
+    };
     match self.field[pos.y][pos.x] {
-      Object::Null | Object::Player => {
-        self.field[self.player_pos.y][self.player_pos.x] = Object::Null;
-        self.field[pos.y][pos.x] = Object::Player;
-        self.player_pos = pos;
-        res = true;
-      },
-      _ => {
-        res = false;
-      }
+      Object::Null => Ok((current, own)),
+      obj if obj == own => Ok((current, own)),
+      obj => Err(FieldError::Occupied(pos, obj)),
     }
-    res
   }
 
-  /// robotをplayerの方向に1移動させます
-  /// playerとの衝突判定は行わないません
-  /// * `robot_type` - 移動させるロボット
-  fn robots_get_closer(&mut self, robot_type: Object) {
-    let vec;
-    match robot_type {
-      Object::Robot => vec = &mut self.robots_pos,
-      Object::SuperRobot => vec = &mut self.super_robots_pos,
-      _ => return,
+  /// 指定の座標にいると次のrobotの移動で倒される可能性があるかを返します
+  /// fieldを変更せずに周囲のマスだけを調べ、robotの隣のマスとsuper_robotから2マス以内のマスを危険とみなします
+  /// robotどうしの衝突で助かる場合も危険とみなします
+  /// * `pos` - 調べる座標
+  pub fn is_threatened(&self, pos: Point) -> bool {
+    if self.hazard_at(pos).is_some() {
+      return true;
     }
-    for idx in 0..vec.len() {
-      let robot = vec[idx];
-      // robotからplayerの距離
-      let mut x = self.player_pos.x as i32 - robot.x as i32;
-      let mut y = self.player_pos.y as i32 - robot.y as i32;
-      // 進む方向の正規化
-      if x != 0 { x /= x.abs(); }
-      if y != 0 { y /= y.abs(); }
+    let xs = pos.x.saturating_sub(2)..=std::cmp::min(pos.x + 2, self.width - 1);
+    let ys = pos.y.saturating_sub(2)..=std::cmp::min(pos.y + 2, self.height - 1);
+    ys.flat_map(|y| xs.clone().map(move |x| Point::new(x, y)))
+      .any(|p| match self.field[p.y][p.x] {
+        Object::Robot | Object::SmartRobot => distance(p, pos) == 1,
+        Object::SuperRobot => true,
+        _ => false,
+      })
+  }
+
+  /// 指定のplayerが移動した後、次のrobotの移動で倒される可能性がないかを返します
+  /// `is_threatened`と同じくfieldを変更せずに調べます
+  /// 移動できない座標の場合は`false`を返します
+  /// * `who` - playerの番号(0: 1人目, 1: 2人目)
+  /// * `pos` - 移動先の座標
+  pub fn is_safe_move(&self, who: usize, pos: Point) -> bool {
+    self.can_move_player(who, pos) && !self.is_threatened(pos)
+  }
+
+  /// 指定のplayerが周囲の8マスへの移動かその場にとどまることで生き残れるかを返します
+  /// * `who` - playerの番号(0: 1人目, 1: 2人目)
+  pub fn has_safe_move(&self, who: usize) -> bool {
+    let pos = match self.player(who) {
+      Some(pos) => pos,
+      None => return false,
+    };
+    let xs = pos.x.saturating_sub(1)..=std::cmp::min(pos.x + 1, self.width - 1);
+    let ys = pos.y.saturating_sub(1)..=std::cmp::min(pos.y + 1, self.height - 1);
+    ys.flat_map(|y| xs.clone().map(move |x| Point::new(x, y)))
+      .any(|p| self.is_safe_move(who, p))
+  }
+
+  /// 2人目のplayerを移動させます
+  /// 指定の座標に移動できないときは`false`を返します
+  /// * `pos` - 移動先の座標
+  #[cfg(test)]
+  pub fn partner_move(&mut self, pos: Point) -> bool {
+    self.try_move_player(1, pos).is_ok()
+  }
+
+  /// playerを移動させます
+  /// 指定の座標に移動できないときは`false`を返します
+  /// * `pos` - 移動先の座標
+  pub fn player_move(&mut self, pos: Point) -> bool {
+    self.try_player_move(pos).is_ok()
+  }
+
+  /// playerを移動させます
+  /// 指定の座標に移動できないときは理由をエラーで返します
+  /// * `pos` - 移動先の座標
+  pub fn try_player_move(&mut self, pos: Point) -> Result<(), FieldError> {
+    self.try_move_player(0, pos)
+  }
+
+  /// 全てのrobotを同時に1マス動かし、衝突したrobotをscrapにします
+  /// 動く前の位置だけから移動先を決めるので、結果はrobotのリストの順番によりません
+  /// 衝突の規則は`resolve_moves`を参照してください
+  /// 倒したrobotの数から計算したscoreを返します
+  /// * `supers_only` - super_robotだけを動かすか(super_robotの2歩目)
+  fn step_robots(&mut self, supers_only: bool) -> u64 {
+    let players = self.living_players();
+    let chase = |from: Point| nearest_player(&players, from).map_or(from, |target| step_toward(from, target));
+    let mut moves: Vec<Move> = Vec::with_capacity(self.robots_pos.len() + self.super_robots_pos.len() + self.smart_robots_pos.len());
+    for &from in &self.robots_pos {
+      moves.push(Move { kind: Object::Robot, from, to: if supers_only { from } else { chase(from) } });
+    }
+    for &from in &self.super_robots_pos {
+      moves.push(Move { kind: Object::SuperRobot, from, to: chase(from) });
+    }
+    // smart_robotは他のrobotの今の位置(fieldから分かる)と、真っすぐ進むrobotの移動先を避ける
+    let targets: HashSet<Point> = if supers_only || self.smart_robots_pos.is_empty() {
+      HashSet::new()
+    } else {
+      moves.iter().filter(|m| m.from != m.to).map(|m| m.to).collect()
+    };
+    for &from in &self.smart_robots_pos {
+      let to = match nearest_player(&players, from) {
+        Some(target) if !supers_only => self.smart_step(from, target, &targets),
+        _ => from,
+      };
+      moves.push(Move { kind: Object::SmartRobot, from, to });
+    }
+    let crashed = resolve_moves(&moves, &self.scraps_pos);
+
+    // 全てのrobotを元の位置から取り除いてから移動先に置くので、fieldは動いたマスだけを更新する
+    for m in &moves {
+      self.field[m.from.y][m.from.x] = Object::Null;
+    }
+    let mut score = 0;
+    self.robots_pos.clear();
+    self.super_robots_pos.clear();
+    self.smart_robots_pos.clear();
+    for (m, crashed) in moves.iter().zip(crashed) {
+      if crashed {
+        self.scraps_pos.insert(m.to);
+        self.field[m.to.y][m.to.x] = Object::Scrap;
+        match m.kind {
+          Object::SuperRobot => {
+            self.report.super_robots += 1;
+            score += 2;
+          },
+          _ => {
+            self.report.robots += 1;
+            score += 1;
+          },
+        }
+        continue;
+      }
+      self.field[m.to.y][m.to.x] = m.kind;
+      match m.kind {
+        Object::SuperRobot => self.super_robots_pos.push(m.to),
+        Object::SmartRobot => self.smart_robots_pos.push(m.to),
+        _ => self.robots_pos.push(m.to),
+      }
+    }
+    score
+  }
 
-      vec[idx].x = (robot.x as i32 + x) as usize;
-      vec[idx].y = (robot.y as i32 + y) as usize;
+  /// smart_robotが次に進むマスを返します
+  /// 真っすぐ進む先にscrapか他のrobotがある場合は、playerに近づける他の隣のマスのうち最もplayerに近いマスに進みます
+  /// 近づけるマスが無い場合はその場にとどまります
+  /// * `robot` - smart_robotの位置
+  /// * `target` - 追いかけるplayerの位置
+  /// * `targets` - 真っすぐ進むrobotの移動先
+  fn smart_step(&self, robot: Point, target: Point, targets: &HashSet<Point>) -> Point {
+    let blocked = |p: &Point| match self.field[p.y][p.x] {
+      Object::Scrap => true,
+      Object::Robot | Object::SuperRobot | Object::SmartRobot => *p != robot,
+      _ => *p != robot && targets.contains(p),
+    };
+    let direct = step_toward(robot, target);
+    if !blocked(&direct) {
+      return direct;
     }
+    let xs = robot.x.saturating_sub(1)..=std::cmp::min(robot.x + 1, self.width - 1);
+    let ys = robot.y.saturating_sub(1)..=std::cmp::min(robot.y + 1, self.height - 1);
+    ys.flat_map(|y| xs.clone().map(move |x| Point::new(x, y)))
+      .filter(|p| !blocked(p) && distance(*p, target) < distance(robot, target))
+      .min_by_key(|p| (distance(*p, target), squared_distance(*p, target)))
+      .unwrap_or(robot)
   }
 
   /// robotをplayerの方向に移動させます
-  /// playerが負けた場合は`None`
+  /// 全てのplayerが負けた場合は`None`
   /// それ以外の場合は獲得したscoreを返します
   /// * `stop` - ゲーム結果が決まるまで動かないことを示すフラグ
   pub fn robots_move(&mut self, stop: bool) -> Option<u64> {
-    // とりあえずロボットを移動させる
-    self.robots_get_closer(Object::Robot);
-    // super_robotsも移動させる
-    self.robots_get_closer(Object::SuperRobot);
-    let mut score = self.check_scrap();   // スクラップにする
+    self.report = TurnReport::default();
+    // 全てのrobotを同時に動かしてスクラップにする
+    let mut score = self.step_robots(false);
     let mut res = self.check_player_pos();// プレイヤーの安全を確認する
 
-    // SuperRobotは2倍移動可能
+    // SuperRobotは2倍移動可能(他のrobotは止まったまま)
     if res {
-      self.robots_get_closer(Object::SuperRobot);
-      score += self.check_scrap();
+      score += self.step_robots(true);
       res = self.check_player_pos();
     }
-    
-
-    // field情報の更新
-    self.field_clear();
-    self.field[self.player_pos.y][self.player_pos.x] = Object::Player;
-    self.field_set(self.robots_pos.clone(), Object::Robot);
-    self.field_set(self.super_robots_pos.clone(), Object::SuperRobot);
-    self.field_set(self.scraps_pos.clone().into_iter().collect(), Object::Scrap);
 
     if res {
       match stop {
@@ -225,6 +781,21 @@ impl Field {
     }
   }
 
+  /// 各objectの位置からfield情報を更新します
+  pub fn field_update(&mut self) {
+    self.field_clear();
+    if self.player_alive {
+      self.field[self.player_pos.y][self.player_pos.x] = Object::Player;
+    }
+    if let (Some(partner), true) = (self.partner_pos, self.partner_alive) {
+      self.field[partner.y][partner.x] = Object::Partner;
+    }
+    self.field_set(self.robots_pos.clone(), Object::Robot);
+    self.field_set(self.super_robots_pos.clone(), Object::SuperRobot);
+    self.field_set(self.smart_robots_pos.clone(), Object::SmartRobot);
+    self.field_set(self.scraps_pos.clone().into_iter().collect(), Object::Scrap);
+  }
+
   /// fieldを`Object::Null`で埋めます
   fn field_clear(&mut self) {
     for y in 0..self.height {
@@ -243,140 +814,190 @@ impl Field {
     }
   }
 
-  /// 衝突したrobotをscrapにします
-  /// 倒したrobotの数から計算した`score`を返します
-  fn check_scrap(&mut self) -> u64 {
-    let mut score = 0;
-
-    // 同じ座標にあるrobotを削除・scrapに追加
-    let rob_num = self.robots_pos.len();
-    let sup_rob_num = self.super_robots_pos.len();
-    let mut rem_idx = Vec::<usize>::new();
-    let mut hash_pos = HashSet::<Point>::new();
-
-    for rob_idx in 0..rob_num {
-      if !hash_pos.contains(&self.robots_pos[rob_idx]) {
-        hash_pos.insert(self.robots_pos[rob_idx]);
-      } else {
-        rem_idx.push(rob_idx);
-        if !self.scraps_pos.contains(&self.robots_pos[rob_idx]) {
-          self.scraps_pos.insert(self.robots_pos[rob_idx]);
-        }
-      }
-    }
-    for rob_idx in rem_idx.iter().rev() {
-      self.robots_pos.remove(*rob_idx);
-      score += 1;
+  /// playerが安全な場所に居るかを判定し、安全でないplayerを倒します
+  /// 生き残っているplayerが居れば`true`
+  /// それ以外なら状態を変更せずに`false`を返します
+  fn check_player_pos(&mut self) -> bool {
+    let player_hazard = self.hazard_at(self.player_pos).filter(|_| self.player_alive);
+    let partner_hazard = match (self.partner_pos, self.partner_alive) {
+      (Some(partner), true) => self.hazard_at(partner),
+      _ => None,
+    };
+    if let Some(hazard) = player_hazard.or(partner_hazard) {
+      self.report.killed_by = Some(hazard);
     }
-    rem_idx = Vec::<usize>::new();
-    for sup_rob_idx in 0..sup_rob_num {
-      if !hash_pos.contains(&self.super_robots_pos[sup_rob_idx]) {
-        hash_pos.insert(self.super_robots_pos[sup_rob_idx]);
-      } else {
-        rem_idx.push(sup_rob_idx);
-        if !self.scraps_pos.contains(&self.super_robots_pos[sup_rob_idx]) {
-          self.scraps_pos.insert(self.super_robots_pos[sup_rob_idx]);
-        }
-      }
-    }
-    for sup_rob_idx in rem_idx.iter().rev() {
-      self.super_robots_pos.remove(*sup_rob_idx);
-      score += 2;
+    let player_safe = self.player_alive && player_hazard.is_none();
+    let partner_safe = self.partner_alive && self.partner_pos.is_some() && partner_hazard.is_none();
+    if player_safe || partner_safe {
+      self.player_alive = player_safe;
+      self.partner_alive = partner_safe;
     }
+    player_safe || partner_safe
+  }
 
-    // scrapと同じ座標にあるrobotを削除
-    let rob_num = self.robots_pos.len();
-    let mut rem_idx: Vec<usize> = Vec::new();
-    for rob_idx in 0..rob_num {
-      if self.scraps_pos.contains(&self.robots_pos[rob_idx]) {
-        rem_idx.push(rob_idx);
-      }
+  /// 指定の座標にあるrobotまたはscrapを返します
+  /// 何もない場合は`None`を返します
+  /// * `pos` - 判定する座標
+  fn hazard_at(&self, pos: Point) -> Option<Object> {
+    // fieldはrobotの移動ごとに更新されているので、座標のマスを見るだけでよい
+    match self.field[pos.y][pos.x] {
+      obj @ (Object::Robot | Object::SuperRobot | Object::SmartRobot | Object::Scrap) => Some(obj),
+      _ => None,
     }
-    for rob_idx in rem_idx.iter().rev() {
-      self.robots_pos.remove(*rob_idx);
-      score += 1;
+  }
+
+  /// fieldの表示範囲をフレーム付きで画面に描画します
+  /// 表示範囲外のrobotはフレーム上に矢印で示します
+  /// * `out` - 描画先
+  /// * `view` - 描画する表示範囲
+  /// * `theme` - 描画に使う記号
+  pub fn print(&self, out: &mut dyn Renderer, view: &Viewport, theme: &Theme) {
+    let x = self.pos.x as i32;
+    let y = self.pos.y as i32;
+    let cw = theme.cell_width();
+
+    let frame: String = std::iter::repeat_n(theme.horizontal, view.width * cw).collect();
+    // フレームの描画
+    out.mv(y-1, x-1);
+    out.addstr(&format!("{}{}{}", theme.corners[0], frame, theme.corners[1]));
+    out.mv(y+view.height as i32, x-1);
+    out.addstr(&format!("{}{}{}", theme.corners[2], frame, theme.corners[3]));
+    // プレイヤーの描画
+    for pos_y in 0..view.height {
+      let row = &self.field[view.offset.y + pos_y][view.offset.x..view.offset.x + view.width];
+      let line: String = row.iter().map(|obj| theme.cell(*obj)).collect();
+      out.mv(y + pos_y as i32, x-1);
+      out.addstr(&format!("{}{}{}", theme.vertical, line, theme.vertical));
     }
-    // scrapと同じ座標にあるsuper_robotを削除
-    let sup_rob_num = self.super_robots_pos.len();
-    let mut rem_idx: Vec<usize> = Vec::new();
-    for rob_idx in 0..sup_rob_num {
-      if self.scraps_pos.contains(&self.super_robots_pos[rob_idx]) {
-        rem_idx.push(rob_idx);
+    // 表示範囲外のrobotの方向を示す
+    for rob in self.robots_pos.iter().chain(self.super_robots_pos.iter()).chain(self.smart_robots_pos.iter()) {
+      if let Some((p, glyph)) = view.edge_indicator(*rob) {
+        // フレーム上の座標を画面の列に変換する
+        let col = match p.x {
+          0 => x - 1,
+          px if px > view.width => x + (view.width * cw) as i32,
+          px => x + ((px - 1) * cw) as i32,
+        };
+        out.mv(y - 1 + p.y as i32, col);
+        out.addstr(&theme.arrow(glyph).to_string());
       }
     }
-    for rob_idx in rem_idx.iter().rev() {
-      self.super_robots_pos.remove(*rob_idx);
-      score += 2;
-    }
-
-    score
   }
 
-  /// playerが安全な場所に居るかを判定します
-  /// playerが安全なら`true`
-  /// それ以外なら`false`を返します
-  fn check_player_pos(&self) -> bool {
-    // playerとrobotの座標を比較
-    let mut res = true;
-    for rob in &self.robots_pos {
-      if *rob == self.player_pos {
-        res = false;
-        break;
-      }
-    }
+}
 
-    // playerとsuper_robotの座標を比較
-    if res {
-      for rob in &self.super_robots_pos {
-        if *rob == self.player_pos {
-          res = false;
-          break;
-        }
+/// 2点間の距離(斜めも1歩と数える)を返します
+/// * `a` - 1つ目の座標
+/// * `b` - 2つ目の座標
+pub(crate) fn distance(a: Point, b: Point) -> usize {
+  std::cmp::max((a.x as i64 - b.x as i64).abs(), (a.y as i64 - b.y as i64).abs()) as usize
+}
+
+/// 2点間の直線距離の2乗を返します
+/// * `a` - 1つ目の座標
+/// * `b` - 2つ目の座標
+fn squared_distance(a: Point, b: Point) -> usize {
+  let dx = a.x as i64 - b.x as i64;
+  let dy = a.y as i64 - b.y as i64;
+  (dx * dx + dy * dy) as usize
+}
+
+/// playerと2人目のplayerの初期位置を返します
+/// playerはfieldの中央、2人目はplayerの右隣(右端の場合は左隣)に配置します
+/// * `width` - fieldの横幅
+/// * `height` - fieldの縦幅
+/// * `partner` - 2人目のplayerを配置するか
+fn start_positions(width: usize, height: usize, partner: bool) -> (Point, Option<Point>) {
+  let player = Point::new(width>>1, height>>1);
+  let partner = match partner {
+    true if player.x + 1 < width => Some(Point::new(player.x + 1, player.y)),
+    true => Some(Point::new(player.x - 1, player.y)),
+    false => None,
+  };
+  (player, partner)
+}
+
+/// `allowed`を満たすマスから重ならないように`n`個のマスをランダムに選びます
+/// 置けるマスが十分に多い場合はランダムな座標を選び直し(fieldの大きさに関わらず速い)、
+/// そうでない場合は置けるマスのリストを先頭の`n`個だけシャッフルします
+/// * `width` - fieldの横幅
+/// * `height` - fieldの縦幅
+/// * `n` - 選ぶマスの数(`free_cells`以下)
+/// * `free_cells` - `allowed`を満たすマスの数
+/// * `allowed` - robotを置けるマスか
+/// * `rng` - 乱数生成器
+fn random_cells<R: Rng, F: Fn(Point) -> bool>(width: usize, height: usize, n: usize, free_cells: usize, allowed: F, rng: &mut R) -> Vec<Point> {
+  if n * 2 <= free_cells && free_cells * 2 >= width * height {
+    // 1回で置けるマスを引く確率は常に1/4以上
+    let mut used = HashSet::with_capacity(n);
+    let mut cells = Vec::with_capacity(n);
+    while cells.len() < n {
+      let p = Point::new(rng.gen_range(0..width), rng.gen_range(0..height));
+      if allowed(p) && used.insert(p) {
+        cells.push(p);
       }
     }
+    return cells;
+  }
+  let mut cells: Vec<Point> = (0..height)
+    .flat_map(|y| (0..width).map(move |x| Point::new(x, y)))
+    .filter(|p| allowed(*p))
+    .collect();
+  for i in 0..n {
+    let j = rng.gen_range(i..cells.len());
+    cells.swap(i, j);
+  }
+  cells.truncate(n);
+  cells
+}
 
-    // playerとscrapの座標を比較
-    if res {
-      for scrap in &self.scraps_pos {
-        if *scrap == self.player_pos {
-          res = false;
-          break;
-        }
-      }
+/// 同時に動いたrobotのうち衝突したものを判定します
+/// 次のrobotは倒れ、その移動先にscrapが残ります
+/// * 同じマスに移動した2体以上のrobot(止まっているrobotのマスに移動した場合も含む)
+/// * scrapのあるマスに移動したrobot
+/// * 互いのマスに入れ替わったrobotや、斜めに交差したrobot(移動の中点が同じrobot)
+///
+/// 他のrobotが出ていくマスに入るのは衝突になりません
+/// 各robotが衝突したかを`moves`と同じ順番で返します
+/// * `moves` - 全てのrobotの動き
+/// * `scraps` - 動く前のscrapの位置
+fn resolve_moves(moves: &[Move], scraps: &HashSet<Point>) -> Vec<bool> {
+  // 移動先と移動の中点ごとにrobotの数を数える
+  let mut arrivals: HashMap<Point, usize> = HashMap::with_capacity(moves.len());
+  let mut passings: HashMap<Point, usize> = HashMap::with_capacity(moves.len());
+  for m in moves {
+    *arrivals.entry(m.to).or_insert(0) += 1;
+    if m.from != m.to {
+      *passings.entry(m.midpoint()).or_insert(0) += 1;
     }
-    res
   }
+  moves.iter().map(|m| {
+    arrivals[&m.to] > 1 || scraps.contains(&m.to) || (m.from != m.to && passings[&m.midpoint()] > 1)
+  }).collect()
+}
 
-  /// fieldをフレーム付きでncursesのウィンドウに描画します
-  pub fn print(&self) {
-    let x = self.pos.x as i32;
-    let y = self.pos.y as i32;
+/// robotから最も近いplayer(同じ距離なら先のplayer)の位置を返します
+/// playerが居ない場合は`None`を返します
+/// * `players` - 生き残っているplayerの位置
+/// * `robot` - robotの位置
+fn nearest_player(players: &[Point], robot: Point) -> Option<Point> {
+  players.iter().copied().min_by_key(|p| distance(*p, robot))
+}
 
-    let mut frame = String::new();
-    for _i in 0..self.width {
-      frame = format!("{}-", &frame);
-    }
-    // フレームの描画
-    mv(y-1, x);
-    addstr(&frame);
-    mv(y+self.height as i32, x);
-    addstr(&frame);
-    // プレイヤーの描画
-    for pos_y in 0..self.height {
-      mv(y + pos_y as i32, x-1);
-      addstr("|");
-      for pos_x in 0..self.width {
-        mv(y + pos_y as i32, x + pos_x as i32);
-        match &self.field[pos_y][pos_x] {
-          Object::Player => addstr("@"),
-          Object::Robot  => addstr("+"),
-          Object::Scrap  => addstr("*"),
-          Object::SuperRobot  => addstr("$"),
-          _              => addstr(" "),
-        };
-      }
-      addstr("|");
-    }
+/// robotが`target`に向かって真っすぐ1マス進んだ位置を返します
+/// * `robot` - robotの位置
+/// * `target` - 追いかけるplayerの位置
+fn step_toward(robot: Point, target: Point) -> Point {
+  // 進む方向の正規化
+  let x = (target.x as i64 - robot.x as i64).signum();
+  let y = (target.y as i64 - robot.y as i64).signum();
+  Point::new((robot.x as i64 + x) as usize, (robot.y as i64 + y) as usize)
+}
+
+/// `{}`では枠なし、`{:#}`では枠付きで`Field::to_ascii`と同じ文字列を出力します
+impl std::fmt::Display for Field {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}", self.to_ascii(f.alternate()))
   }
 }
 
@@ -436,6 +1057,172 @@ mod tests {
     assert_eq!(Point::new(1, 0), field.player_pos);
   }
 
+#[test]
+  fn from_rows_test() {
+    let field = Field::from_rows(&["+.$", ".@*", "&.."], Point::new(5, 5)).unwrap();
+    assert_eq!((field.width, field.height), (3, 3));
+    assert_eq!(field.player_pos, Point::new(1, 1));
+    assert_eq!(field.partner_pos, Some(Point::new(0, 2)));
+    assert_eq!(field.robots_pos, vec![Point::new(0, 0)]);
+    assert_eq!(field.super_robots_pos, vec![Point::new(2, 0)]);
+    assert_eq!(field.field[1][2], Object::Scrap);
+
+    assert!(Field::from_rows(&["+..", ".."], Point::new(0, 0)).is_none());
+    assert!(Field::from_rows(&["+.."], Point::new(0, 0)).is_none());
+    assert!(Field::from_rows(&["@@."], Point::new(0, 0)).is_none());
+    assert!(Field::from_rows(&["@x."], Point::new(0, 0)).is_none());
+  }
+
+#[test]
+  fn ascii_test() {
+    let field = Field::from_parts(Point::new(0, 0), 4, 2, Point::new(1, 1),
+      vec![Point::new(0, 0)], vec![Point::new(3, 1)], [Point::new(2, 0)].iter().copied().collect());
+    assert_eq!(field.to_ascii(false), "+.*.\n.@.$\n");
+    assert_eq!(format!("{:#}", field), "------\n|+.*.|\n|.@.$|\n------\n");
+    assert_eq!(format!("{}", field), field.to_ascii(false));
+
+    for frame in [false, true].iter() {
+      let parsed = Field::from_ascii(&field.to_ascii(*frame), Point::new(0, 0)).unwrap();
+      assert_eq!(parsed.to_ascii(false), field.to_ascii(false));
+      assert_eq!(parsed.robots_pos, field.robots_pos);
+    }
+    assert!(Field::from_ascii("---\n|@.\n---", Point::new(0, 0)).is_none());
+    assert!(Field::from_ascii("", Point::new(0, 0)).is_none());
+  }
+
+#[test]
+  fn robots_move_picture_test() {
+    let mut field = Field::from_ascii("
+      +....+
+      ......
+      ..@...
+      ......
+      +.....
+    ", Point::new(0, 0)).unwrap();
+    assert_eq!(field.robots_move(false), Some(0));
+    assert_eq!(field.to_ascii(false), "\
+......
+.+..+.
+..@...
+.+....
+......
+", "{:#}", field);
+
+    // 同じマスに移動したrobotはscrapになる
+    let mut field = Field::from_ascii("
+      +.+
+      ...
+      .@.
+    ", Point::new(0, 0)).unwrap();
+    assert_eq!(field.robots_move(false), Some(2));
+    assert_eq!(field.to_ascii(false), "...\n.*.\n.@.\n", "{:#}", field);
+  }
+
+#[test]
+  fn try_new_test() {
+    assert_eq!(Field::try_new(Point::new(0, 0), 0, 10, 0).unwrap_err(), FieldError::EmptyField { width: 0, height: 10 });
+    assert_eq!(Field::try_new(Point::new(0, 0), 3, 3, 9).unwrap_err(), FieldError::TooManyRobots { robots: 9, free_cells: 8 });
+    let field = Field::try_new(Point::new(0, 0), 3, 3, 8).unwrap();
+    assert_eq!(field.robots_pos.len() + field.super_robots_pos.len(), 8);
+
+    let mut rng = rand::thread_rng();
+    assert!(Field::try_with_partner(Point::new(0, 0), 1, 5, 0, &mut rng).is_err());
+    assert_eq!(Field::try_with_partner(Point::new(0, 0), 3, 3, 8, &mut rng).unwrap_err(),
+      FieldError::TooManyRobots { robots: 8, free_cells: 7 });
+  }
+
+#[test]
+  fn placement_test() {
+    let mut rng = rand::thread_rng();
+    let placement = Placement { super_robot_ratio: 3, smart_robot_ratio: 0, min_distance: 1 };
+    let field = Field::try_with_placement(Point::new(0, 0), 10, 10, 30, &mut rng, false, &placement).unwrap();
+    assert_eq!((field.robots_pos.len(), field.super_robots_pos.len(), field.smart_robots_pos.len()), (20, 10, 0));
+    let placement = Placement { super_robot_ratio: 0, smart_robot_ratio: 3, min_distance: 1 };
+    let field = Field::try_with_placement(Point::new(0, 0), 10, 10, 30, &mut rng, true, &placement).unwrap();
+    assert_eq!((field.robots_pos.len(), field.super_robots_pos.len(), field.smart_robots_pos.len()), (20, 0, 10));
+    assert!(field.partner_pos.is_some());
+  }
+
+#[test]
+  fn min_distance_test() {
+    let mut rng = rand::thread_rng();
+    let placement = Placement { super_robot_ratio: 10, smart_robot_ratio: 0, min_distance: 3 };
+    // 5x5の正方形を除いた全てのマスに置く(シャッフルで選ぶ)
+    assert_eq!(Field::free_cells(11, 11, false, 3), 96);
+    let field = Field::try_with_placement(Point::new(0, 0), 11, 11, 96, &mut rng, false, &placement).unwrap();
+    let robots: HashSet<Point> = field.robots_pos.iter().chain(field.super_robots_pos.iter()).copied().collect();
+    assert_eq!(robots.len(), 96);
+    assert!(robots.iter().all(|p| distance(*p, field.player_pos) >= 3));
+    assert_eq!(Field::try_with_placement(Point::new(0, 0), 11, 11, 97, &mut rng, false, &placement).unwrap_err(),
+      FieldError::TooManyRobots { robots: 97, free_cells: 96 });
+
+    // 2人のplayerの両方から離す(座標を選び直す)
+    assert_eq!(Field::free_cells(40, 20, true, 2), 800 - 12);
+    let field = Field::try_with_placement(Point::new(0, 0), 40, 20, 100, &mut rng, true, &placement).unwrap();
+    let partner = field.partner_pos.unwrap();
+    assert!(field.robots_pos.iter().all(|p| distance(*p, field.player_pos) >= 3 && distance(*p, partner) >= 3));
+
+    // 大きなfieldでも全てのマスを並べずに配置できる
+    let field = Field::try_with_placement(Point::new(0, 0), 1000, 1000, 50000, &mut rng, false, &Placement::default()).unwrap();
+    let robots: HashSet<Point> = field.robots_pos.iter().chain(field.super_robots_pos.iter()).copied().collect();
+    assert_eq!(robots.len(), 50000);
+    assert!(!robots.contains(&field.player_pos));
+  }
+
+#[test]
+  fn all_robots_destroyed_test() {
+    // super_robotやsmart_robotが残っている間はクリアにならない
+    for row in ["+.@", "$.@", "%.@"].iter() {
+      let field = Field::from_rows(&[row], Point::new(0, 0)).unwrap();
+      assert!(!field.all_robots_destroyed(), "{}", row);
+    }
+    let field = Field::from_rows(&["*.@"], Point::new(0, 0)).unwrap();
+    assert!(field.all_robots_destroyed());
+  }
+
+#[test]
+  fn smart_robots_move_test() {
+    // 真っすぐ進む先のscrapを避けて回り込む
+    let mut field = Field::from_rows(&[
+      ".....",
+      "%*..@",
+      ".....",
+    ], Point::new(0, 0)).unwrap();
+    assert_eq!(field.robots_move(false), Some(0));
+    assert_eq!(field.to_ascii(false), ".%...\n.*..@\n.....\n", "{:#}", field);
+
+    // 普通のrobotはそのままscrapに突っ込む
+    let mut field = Field::from_rows(&[
+      "+*..@",
+    ], Point::new(0, 0)).unwrap();
+    assert_eq!(field.robots_move(false), Some(1));
+    assert!(field.robots_pos.is_empty());
+
+    // 他のrobotの移動先を避け、近づけるマスが無ければとどまる
+    let mut field = Field::from_rows(&[
+      "+%*",
+      "***",
+      "..@",
+    ], Point::new(0, 0)).unwrap();
+    assert_eq!(field.robots_move(false), Some(1));
+    assert_eq!(field.smart_robots_pos, vec![Point::new(1, 0)]);
+    assert_eq!(field.report.killed_by, None);
+  }
+
+#[test]
+  fn try_player_move_test() {
+    let mut field = Field::new(Point::new(0, 0), 10, 5, 0);
+    assert_eq!(field.try_player_move(Point::new(10, 0)), Err(FieldError::OutOfBounds(Point::new(10, 0))));
+    assert_eq!(field.try_player_move(Point::new(0, 5)), Err(FieldError::OutOfBounds(Point::new(0, 5))));
+    assert!(!field.player_move(Point::new(99, 99)));
+
+    field.field[0][1] = Object::Scrap;
+    assert_eq!(field.try_player_move(Point::new(1, 0)), Err(FieldError::Occupied(Point::new(1, 0), Object::Scrap)));
+    assert_eq!(field.try_move_player(1, Point::new(2, 0)), Err(FieldError::NoPlayer(1)));
+    assert_eq!(field.try_player_move(Point::new(2, 0)), Ok(()));
+    assert_eq!(field.player_pos, Point::new(2, 0));
+  }
+
 #[test]
   fn robots_move_test() {
     let mut field = Field::new(Point::new(0, 0), 50, 20, 0);
@@ -457,15 +1244,156 @@ mod tests {
     field.player_move(Point::new(8, 8));
     assert_eq!(field.robots_move(false), None);
     assert_eq!(field.robots_pos[0], Point::new(8, 8));
+    assert_eq!(field.report.killed_by, Some(Object::Robot));
 
     field.robots_pos[0] = Point::new(10, 10);
     field.robots_pos.push(Point::new(12, 10));
     field.player_move(Point::new(11, 0));
     assert_eq!(field.robots_move(false), Some(2));
+    assert_eq!(field.report, TurnReport { robots: 2, super_robots: 0, killed_by: None });
     assert_eq!(field.robots_pos.len(), 0);
     assert!(field.scraps_pos.contains(&Point::new(11, 9)))
   }
 
+#[test]
+  fn safe_move_test() {
+    let field = Field::from_rows(&[
+      "+....",
+      "..@..",
+      ".....",
+    ], Point::new(0, 0)).unwrap();
+    assert!(!field.is_safe_move(0, Point::new(1, 1)));
+    assert!(!field.is_safe_move(0, Point::new(1, 0)));
+    assert!(field.is_safe_move(0, Point::new(3, 2)));
+    assert!(field.has_safe_move(0));
+
+    let field = Field::from_rows(&[
+      "+.+",
+      ".@.",
+      "+.+",
+    ], Point::new(0, 0)).unwrap();
+    assert!(!field.has_safe_move(0));
+  }
+
+#[test]
+  fn threatened_test() {
+    let field = Field::from_rows(&[
+      "+.....",
+      "..@...",
+      ".....$",
+    ], Point::new(0, 0)).unwrap();
+    assert!(field.is_threatened(Point::new(1, 1)));
+    assert!(!field.is_threatened(Point::new(2, 1)));
+    assert!(field.is_threatened(Point::new(3, 0)));
+    assert!(!field.is_threatened(Point::new(2, 2)));
+    assert!(field.is_threatened(Point::new(0, 0)));
+    assert!(field.can_move_player(0, Point::new(2, 1)));
+    assert!(!field.can_move_player(0, Point::new(0, 0)));
+    assert!(!field.can_move_player(1, Point::new(1, 1)));
+  }
+
+#[test]
+  fn resolve_moves_test() {
+    let mv = |from: (usize, usize), to: (usize, usize)| Move {
+      kind: Object::Robot, from: Point::new(from.0, from.1), to: Point::new(to.0, to.1),
+    };
+    let scraps: HashSet<Point> = [Point::new(5, 5)].iter().copied().collect();
+    // 入れ替わり
+    assert_eq!(resolve_moves(&[mv((1, 1), (2, 1)), mv((2, 1), (1, 1))], &scraps), vec![true, true]);
+    // 斜めの交差
+    assert_eq!(resolve_moves(&[mv((1, 1), (2, 2)), mv((2, 1), (1, 2))], &scraps), vec![true, true]);
+    // 出ていくマスに続けて入るのは衝突しない
+    assert_eq!(resolve_moves(&[mv((1, 1), (2, 1)), mv((2, 1), (3, 1))], &scraps), vec![false, false]);
+    // 同じ向きに並んで斜めに進むのも衝突しない
+    assert_eq!(resolve_moves(&[mv((1, 1), (2, 2)), mv((2, 1), (3, 2))], &scraps), vec![false, false]);
+    // 止まっているrobotのマスへの移動とscrapへの移動
+    assert_eq!(resolve_moves(&[mv((3, 3), (3, 3)), mv((2, 3), (3, 3)), mv((4, 4), (5, 5))], &scraps), vec![true, true, true]);
+    // 止まっているrobotどうしは衝突しない
+    assert_eq!(resolve_moves(&[mv((3, 3), (3, 3)), mv((4, 3), (4, 3))], &scraps), vec![false, false]);
+  }
+
+#[test]
+  fn robots_move_order_test() {
+    // 2体の順番を入れ替えても結果は同じ
+    let rows = [
+      ".+.+.",
+      ".....",
+      "..@..",
+      ".....",
+      "%...%",
+    ];
+    let field = Field::from_rows(&rows, Point::new(0, 0)).unwrap();
+    let mut reversed = field.clone();
+    reversed.robots_pos.reverse();
+    reversed.smart_robots_pos.reverse();
+    let mut field = field;
+    assert_eq!(field.robots_move(false), Some(2));
+    assert_eq!(reversed.robots_move(false), Some(2));
+    assert_eq!(field.to_ascii(false), ".....\n..*..\n..@..\n.%.%.\n.....\n", "{:#}", field);
+    assert_eq!(field.to_ascii(false), reversed.to_ascii(false));
+    assert_eq!(field.report, reversed.report);
+  }
+
+#[test]
+  fn super_robot_second_step_test() {
+    // 1歩目は出ていくrobotに続いて進み、2歩目で止まっているrobotに衝突する
+    let mut field = Field::from_rows(&[
+      "$....",
+      ".+...",
+      ".....",
+      ".....",
+      "....@",
+    ], Point::new(0, 0)).unwrap();
+    assert_eq!(field.robots_move(false), Some(3));
+    assert_eq!(field.to_ascii(false), ".....\n.....\n..*..\n.....\n....@\n", "{:#}", field);
+    assert_eq!(field.report, TurnReport { robots: 1, super_robots: 1, killed_by: None });
+  }
+
+#[test]
+  fn keymap_test() {
+    let p1 = KeyMap::player1();
+    let p2 = KeyMap::player2();
+    assert_eq!(p1.translate(KEY_UP), KEY_UP);
+    assert_eq!(p1.translate(b'e' as i32), ERR);
+    assert_eq!(p2.translate(b'e' as i32), KEY_UP);
+    assert_eq!(p2.translate(b'd' as i32), KEY_RAND);
+    assert_eq!(p2.translate(KEY_UP), ERR);
+    assert_eq!(p2.translate(KEY_QUIT), KEY_QUIT);
+  }
+
+#[test]
+  fn coop_robots_move_test() {
+    let mut rng = rand::thread_rng();
+    let mut field = Field::with_partner(Point::new(0, 0), 50, 20, 0, &mut rng);
+    assert_eq!(field.partner_pos, Some(Point::new(26, 10)));
+    assert!(matches!(field.field[10][26], Object::Partner));
+    assert!(!field.player_move(Point::new(26, 10)));
+    assert!(!field.partner_move(Point::new(25, 10)));
+
+    // robotは近い方のplayerを追いかける
+    field.player_move(Point::new(0, 0));
+    field.partner_move(Point::new(40, 10));
+    field.robots_pos.push(Point::new(35, 15));
+    field.robots_pos.push(Point::new(5, 5));
+    assert_eq!(field.robots_move(false), Some(0));
+    assert_eq!(field.robots_pos, vec![Point::new(36, 14), Point::new(4, 4)]);
+
+    // 1人が倒されても、もう1人が生き残っていれば続く
+    field.player_move(Point::new(3, 3));
+    assert_eq!(field.robots_move(false), Some(0));
+    assert!(!field.player_alive);
+    assert!(field.partner_alive);
+    assert_eq!(field.living_players(), vec![Point::new(40, 10)]);
+    assert_eq!(field.player(0), None);
+
+    // 倒されたplayerは追いかけない
+    assert_eq!(field.robots_move(false), Some(0));
+    assert_eq!(field.robots_pos[1], Point::new(4, 4));
+
+    field.partner_move(Point::new(37, 12));
+    assert_eq!(field.robots_move(false), None);
+  }
+
 #[test]
   fn field_clear_test() {
     let mut field = Field::new(Point::new(0, 0), 50, 20, 40);
@@ -475,9 +1403,8 @@ mod tests {
     let mut null_count = 0;
     for y in 0..field.height {
       for x in 0..field.width {
-        match field.field[y][x] {
-          Object::Null => { null_count += 1; },
-          _ => (),
+        if let Object::Null = field.field[y][x] {
+          null_count += 1;
         }
       }
     }
@@ -495,12 +1422,104 @@ mod tests {
 
     field.field_set(field.robots_pos.clone(), Object::Robot);
     for i in 0..10 {
-      assert!(match field.field[i][i] {
-        Object::Robot => true,
-        _ => false,
-      });
+      assert!(matches!(field.field[i][i], Object::Robot));
     }
 
   }
 
+  /// robotの多いfieldを生成します(playerの周囲2マスにはrobotを置かない)
+  fn crowded_field(width: usize, height: usize, robots: usize, seed: u64) -> Field {
+    use rand::SeedableRng;
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(seed);
+    let placement = Placement { super_robot_ratio: 10, smart_robot_ratio: 20, min_distance: 3 };
+    Field::try_with_placement(Point::new(0, 0), width, height, robots, &mut rng, false, &placement).unwrap()
+  }
+
+  /// 描画された文字を記録する画面
+  struct Screen {
+    /// 各行の文字
+    rows: Vec<Vec<char>>,
+    /// カーソルの位置(行, 列)
+    cursor: (i32, i32),
+  }
+
+  impl Renderer for Screen {
+    fn size(&self) -> (i32, i32) {
+      (self.rows[0].len() as i32, self.rows.len() as i32)
+    }
+    fn mv(&mut self, y: i32, x: i32) {
+      self.cursor = (y, x);
+    }
+    fn addstr(&mut self, s: &str) {
+      for c in s.chars() {
+        let (y, x) = self.cursor;
+        self.rows[y as usize][x as usize] = c;
+        self.cursor.1 += 1;
+      }
+    }
+    fn clrtoeol(&mut self) {}
+    fn clear(&mut self) {}
+    fn refresh(&mut self) {}
+  }
+
+#[test]
+  fn print_edge_indicator_test() {
+    let mut field = Field::from_rows(&[
+      "..........",
+      "..@......%",
+      "..........",
+    ], Point::new(1, 1)).unwrap();
+    field.smart_robots_pos.push(Point::new(5, 2));
+    field.field_update();
+    let view = Viewport { offset: Point::new(0, 0), width: 4, height: 3 };
+    let mut screen = Screen { rows: vec![vec![' '; 10]; 6], cursor: (0, 0) };
+    field.print(&mut screen, &view, &Theme::ascii());
+    let rows: Vec<String> = screen.rows.iter().map(|row| row.iter().collect()).collect();
+    // 表示範囲外のsmart_robotも右の枠に矢印で示す
+    assert_eq!(rows[1], "|    |    ");
+    assert_eq!(rows[2], "|  @ >    ");
+    assert_eq!(rows[3], "|    >    ");
+  }
+
+#[test]
+  fn field_index_test() {
+    // robotの移動ごとに更新したfieldは、位置のリストから作り直したfieldと同じ
+    let mut field = crowded_field(60, 30, 200, 1);
+    assert!(!field.smart_robots_pos.is_empty());
+    let mut turns = 0;
+    while field.robots_move(false).is_some() {
+      let mut rebuilt = field.clone();
+      rebuilt.field_update();
+      assert_eq!(field.to_ascii(false), rebuilt.to_ascii(false));
+      turns += 1;
+    }
+    assert!(turns > 0);
+    assert!(field.report.killed_by.is_some());
+  }
+
+  /// `step_bench`で許す1ターンの時間の上限
+  /// 現在の実装では20ms程度なので、遅いマシンでも失敗しないように余裕を持たせています
+  const STEP_BENCH_LIMIT: std::time::Duration = std::time::Duration::from_millis(250);
+
+  /// 1000x1000のfieldで30000体のrobotを動かす時間を測ります
+  /// `cargo test --release -- --ignored step_bench --nocapture`で実行します
+  /// releaseビルドでは1ターンが`STEP_BENCH_LIMIT`を超えると失敗します
+#[test]
+#[ignore]
+  fn step_bench() {
+    let mut field = crowded_field(1000, 1000, 30000, 42);
+    let turns = 20;
+    let start = std::time::Instant::now();
+    for _ in 0..turns {
+      field.robots_move(false);
+    }
+    let elapsed = start.elapsed();
+    println!("{} turns, {:?} per turn, {} robots left, {} scraps",
+      turns, elapsed / turns, field.robots_pos.len() + field.super_robots_pos.len(), field.scraps_pos.len());
+    // debugビルドは最適化されず遅いので、時間を確かめるのはreleaseビルドだけにする
+    if !cfg!(debug_assertions) {
+      assert!(elapsed / turns < STEP_BENCH_LIMIT, "{:?} per turn exceeds {:?}", elapsed / turns, STEP_BENCH_LIMIT);
+    }
+  }
+
 }
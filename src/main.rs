@@ -1,106 +1,910 @@
 //! 本プログラムは授業用に制作したRobotsのプログラムです
 
-use rand::Rng;
-use ncurses::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use std::fs;
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
 
 mod internal;
+mod config;
+mod viewport;
+mod save;
+mod net;
+mod spectate;
+mod score;
+mod stats;
+mod achievements;
+mod daily;
+mod puzzle;
+mod term;
+mod theme;
+mod accessible;
+mod help;
+mod menu;
+mod i18n;
+mod cli;
+mod difficulty;
 use internal::*;
 use internal::{KEY_UP, KEY_DOWN, KEY_LEFT, KEY_RIGHT};
-
-/// endwin()を呼んでmain()からreturnする
-macro_rules! exit {
-  () => {
-    endwin();
-    return;
-  }
-}
+use config::{Config, REALTIME_TICK_MS};
+use viewport::*;
+use save::SaveData;
+use net::{Peer, PeerState};
+use spectate::Broadcaster;
+use score::{ScoreBreakdown, ScoreEngine};
+use stats::Stats;
+use achievements::{Achievement, Achievements, Event};
+use daily::DailyTable;
+use term::{Terminal, ERR};
+use theme::Theme;
+use menu::Menu;
+use i18n::{Lang, Msg};
+use cli::Command;
+use difficulty::{Difficulty, Rules};
 
 /// ステータスの位置にlevel, scoreを表示する
 macro_rules! print_status {
-  ($level:expr, $score:expr) => {
-    mv(3, 0);
-    addstr(&format!("level: {}, score: {}", $level, $score));
+  ($term:expr, $lang:expr, $level:expr, $score:expr) => {
+    $term.mv(3, 0);
+    $term.addstr(&Msg::Status($level, $score).text($lang));
+    $term.clrtoeol();
   }
 }
 
 /// プレイ結果の位置にリザルトを表示する
 macro_rules! print_result {
-  ($res: expr) => {
-    mv(1, 0);
-    addstr($res);
+  ($term:expr, $res: expr) => {
+    $term.mv(1, 0);
+    $term.addstr($res);
+    $term.clrtoeol();
   }
 }
 
+/// 中断したゲームを保存するファイル名
+const SAVE_FILE: &str = "save.dat";
+/// 累計の記録を保存するファイル名
+const STATS_FILE: &str = "stats.dat";
+/// 獲得した実績を保存するファイル名
+const ACHIEVEMENTS_FILE: &str = "achievements.dat";
+/// デイリーモードのハイスコア表のファイル名
+const DAILY_FILE: &str = "daily.dat";
+/// 累計の記録を表示する
+const KEY_STATS: i32 = b't' as i32;
+/// 実績の一覧を表示する
+const KEY_ACHIEVEMENTS: i32 = b'h' as i32;
+/// 操作方法を表示する
+const KEY_HELP: i32 = b'?' as i32;
+/// ゲームを一時停止してメニューを表示する
+const KEY_PAUSE: i32 = b'p' as i32;
+/// パズルをやり直す
+const KEY_RETRY: i32 = b'r' as i32;
+/// 次のパズルに進む
+const KEY_NEXT: i32 = b'n' as i32;
+/// 対戦中に相手の状態を確認する間隔(ミリ秒)
+const PEER_POLL_MS: i32 = 200;
+/// アクセシブルモードでfieldの下に表示する説明の行数
+const SUMMARY_LINES: i32 = 3;
+/// fieldを描画する画面上の位置
+const FIELD_POS: Point = Point{x:5, y:5};
+/// ハイスコアの画面に表示する記録の数
+const HIGH_SCORES_SHOWN: usize = 10;
+
+/// タイトル画面で選ばれた始め方を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Start {
+  /// 新しいゲームを始める
+  NewGame,
+  /// 中断したゲームを再開する
+  Continue,
+  /// パズルモードを始める
+  Puzzles,
+}
+
 fn main() {
-  initscr();
-  noecho();
-  nonl();
-  intrflush(stdscr(), true);
-  keypad(stdscr(), true);
-  addstr("***Robots***");
-  refresh();
+  let args: Vec<String> = std::env::args().collect();
+  let cli = match cli::parse(&args[1..]) {
+    Ok(cli) => cli,
+    Err(e) => {
+      let lang = Lang::from_env();
+      eprintln!("{}", Msg::CliError(&e).text(lang));
+      eprintln!("{}", Msg::UsageHint.text(lang));
+      std::process::exit(2);
+    },
+  };
+  let mut config = cli.config;
+  let lang = config.lang;
+  if cli.help {
+    println!("{}", Msg::Usage.text(lang));
+    return;
+  }
 
-  let mut score:u64 = 0;
-  let mut level:u32 = 1;
+  match cli.command {
+    Some(Command::Stats) => {
+      for (stat, value) in stats::load(STATS_FILE).rows() {
+        println!("{}{:>10}", i18n::pad(&Msg::Stat(stat).text(lang), 24), value);
+      }
+      return;
+    },
+    Some(Command::Achievements) => {
+      let achievements = Achievements::load(ACHIEVEMENTS_FILE, &config.profile);
+      for a in Achievement::ALL.iter() {
+        let mark = if achievements.is_unlocked(*a) { 'x' } else { ' ' };
+        println!("[{}] {}{}", mark, i18n::pad(&Msg::AchievementTitle(*a).text(lang), 20),
+          Msg::AchievementDescription(*a).text(lang));
+      }
+      return;
+    },
+    Some(Command::Daily) => {
+      let date = daily::date_string(daily::today());
+      println!("{}", Msg::DailyRanking(&date).text(lang));
+      for (i, entry) in DailyTable::load(DAILY_FILE).ranking(&date).iter().enumerate() {
+        println!("{:>3}. {:<20}{:>10}", i + 1, entry.profile, entry.score);
+      }
+      return;
+    },
+    _ => (),
+  }
 
-  let mut scr_w: i32 = 0;
-  let mut scr_h: i32 = 0;
-  getmaxyx(stdscr(), &mut scr_h, &mut scr_w);
+  let mut backend = match term::open(cli.ansi) {
+    Ok(backend) => backend,
+    Err(e) => {
+      eprintln!("{}", Msg::TerminalInitFailed(&e).text(lang));
+      return;
+    },
+  };
+  let term: &mut dyn Terminal = &mut *backend;
+  term.addstr("***Robots***");
+  term.refresh();
 
+  // 対戦相手と接続する
+  let peer = match &cli.command {
+    Some(Command::Host(port)) => {
+      let port = *port;
+      print_result!(term, &Msg::WaitingForOpponent(port).text(lang));
+      term.refresh();
+      match TcpListener::bind(("0.0.0.0", port)).and_then(|listener| Peer::host(&listener)) {
+        Ok(peer) => Some(peer),
+        Err(e) => {
+          print_result!(term, &Msg::HostFailed(&e).text(lang));
+          term.getch();
+          return;
+        },
+      }
+    },
+    Some(Command::Watch(addr)) => {
+      watch(term, &config, addr);
+      return;
+    },
+    Some(Command::Puzzle(start)) => {
+      play_puzzles(term, &config, start - 1);
+      return;
+    },
+
+    Some(Command::Join(addr)) => {
+      match Peer::join(addr) {
+        Ok(peer) => Some(peer),
+        Err(e) => {
+          print_result!(term, &Msg::JoinFailed(addr, &e).text(lang));
+          term.getch();
+          return;
+        },
+      }
+    },
+    Some(Command::Coop) | Some(Command::Stats) | Some(Command::Achievements) | Some(Command::Daily) | None => None,
+  };
+
+  // 観戦者への配信を開始する
+  let mut spectators = None;
+  if let Some(addr) = &cli.spectate {
+    match Broadcaster::bind(addr) {
+      Ok(broadcaster) => {
+        print_result!(term, &format!("{} {}", Msg::SpectateReady(&broadcaster.addr).text(lang), Msg::PressAnyKey.text(lang)));
+        term.getch();
+        spectators = Some(broadcaster);
+      },
+      Err(e) => {
+        print_result!(term, &Msg::SpectateFailed(addr, &e).text(lang));
+        term.getch();
+        return;
+      },
+    }
+  }
+
+  // サブコマンドが無ければタイトル画面を表示し、ゲームが終わるとタイトル画面に戻る
+  let show_menu = cli.command.is_none() && !cli.resume;
+  let mut peer = peer;
   loop {
-    let mut field = Field::new(Point{x:5, y:5}, (scr_w-8) as usize, (scr_h-6) as usize, std::cmp::min((level*5) as usize, 40));
-    field.print();
+    let start = if show_menu {
+      match title_menu(term, &mut config) {
+        Some(Start::Puzzles) => {
+          play_puzzles(term, &config, 0);
+          continue;
+        },
+        Some(start) => Some(start),
+        None => return,
+      }
+    } else {
+      None
+    };
+
+    // 中断したゲームを再開する
+    let mut resumed = None;
+    if peer.is_none() && config.daily.is_none() && std::path::Path::new(SAVE_FILE).exists() {
+      let resume = match start {
+        Some(start) => start == Start::Continue,
+        None => cli.resume || {
+          print_result!(term, &Msg::ResumePrompt.text(config.lang));
+          term.getch() == KEY_YES
+        },
+      };
+      if resume {
+        match save::load_game(SAVE_FILE, FIELD_POS) {
+          Ok(data) => {
+            // 続きのlevelも保存したときの難易度で遊ぶ
+            config.difficulty = data.rules.difficulty;
+            resumed = Some(data);
+            save::remove_save(SAVE_FILE);
+          },
+          // 壊れたセーブファイルは確認してから削除する
+          Err(e) if e.is_corrupt() => {
+            print_result!(term, &format!("{} {}", Msg::LoadFailed(&e).text(config.lang), Msg::DeleteSavePrompt.text(config.lang)));
+            if term.getch() == KEY_YES {
+              save::remove_save(SAVE_FILE);
+            }
+          },
+          // 読み込めなかっただけの場合や新しい形式の場合は残しておく
+          Err(e) => {
+            print_result!(term, &Msg::LoadFailed(&e).text(config.lang));
+            term.getch();
+          },
+        }
+      }
+    }
+
+    let session = play(term, &config, resumed, peer.take(), &mut spectators);
+    let recorded = stats::record(STATS_FILE, &session);
+    if !show_menu {
+      drop(backend);
+      if let Err(e) = recorded {
+        eprintln!("{}", Msg::StatsSaveFailed(&e).text(config.lang));
+      }
+      return;
+    }
+    if let Err(e) = recorded {
+      print_result!(term, &format!("{} {}", Msg::StatsSaveFailed(&e).text(config.lang), Msg::PressAnyKey.text(config.lang)));
+      term.getch();
+    }
+  }
+}
+
+/// タイトル画面のメニューを表示し、ゲームの始め方が選ばれるまで続けます
+/// 遊び方と設定の変更は`config`に反映します
+/// 終了が選ばれた場合は`None`を返します
+/// * `term` - 描画と入力に使う端末
+/// * `config` - ゲームの設定
+fn title_menu(term: &mut dyn Terminal, config: &mut Config) -> Option<Start> {
+  let mut puzzles = false;
+  let mut selected = 0;
+  term.timeout(-1);
+  loop {
+    let lang = config.lang;
+    let mode = match (puzzles, config.coop, config.daily, config.realtime) {
+      (true, _, _, _) => 4,
+      (_, true, _, _) => 3,
+      (_, _, Some(_), _) => 2,
+      (_, _, _, Some(_)) => 1,
+      _ => 0,
+    };
+    let items = vec![
+      Msg::NewGame.text(lang),
+      Msg::Continue.text(lang),
+      Msg::GameMode(&Msg::Mode(mode).text(lang)).text(lang),
+      Msg::HighScores.text(lang),
+      Msg::Options.text(lang),
+      Msg::Quit.text(lang),
+    ];
+    let mut menu = Menu::new("Robots", items);
+    menu.selected = selected;
+    selected = menu.choose(term, lang)?;
+    match selected {
+      0 if puzzles => return Some(Start::Puzzles),
+      0 => return Some(Start::NewGame),
+      1 if std::path::Path::new(SAVE_FILE).exists() => return Some(Start::Continue),
+      1 => {
+        print_result!(term, &format!("{} {}", Msg::NoSavedGame.text(lang), Msg::PressAnyKey.text(lang)));
+        term.getch();
+      },
+      2 => { puzzles = game_mode_menu(term, config).unwrap_or(puzzles); },
+      3 => show_high_scores(term, &config.score_file, lang),
+      4 => options_menu(term, config),
+      _ => return None,
+    }
+  }
+}
+
+/// 遊び方を選ぶメニューを表示し、選ばれた遊び方を`config`に反映します
+/// パズルモードが選ばれたかを返し、何も選ばれなかった場合は`None`を返します
+/// * `term` - 描画と入力に使う端末
+/// * `config` - ゲームの設定
+fn game_mode_menu(term: &mut dyn Terminal, config: &mut Config) -> Option<bool> {
+  let lang = config.lang;
+  let items = (0..5).map(|mode| Msg::Mode(mode).text(lang)).collect();
+  let mode = Menu::new(&Msg::GameModeTitle.text(lang), items).choose(term, lang)?;
+  // 協力プレイは2人分のマスを空けるので、robotが収まるかを調べ直す
+  if let Err(e) = cli::check_robots(&Config { coop: mode == 3, ..config.clone() }) {
+    print_result!(term, &format!("{} {}", Msg::CliError(&e).text(lang), Msg::PressAnyKey.text(lang)));
+    term.getch();
+    return None;
+  }
+  config.realtime = if mode == 1 { Some(config.realtime.unwrap_or(REALTIME_TICK_MS)) } else { None };
+  config.daily = if mode == 2 { Some(daily::today()) } else { None };
+  config.coop = mode == 3;
+  Some(mode == 4)
+}
+
+/// 表示の設定を切り替えるメニューを表示します
+/// * `term` - 描画と入力に使う端末
+/// * `config` - ゲームの設定
+fn options_menu(term: &mut dyn Terminal, config: &mut Config) {
+  let mut selected = 0;
+  let mut message = None;
+  loop {
+    let lang = config.lang;
+    let unicode = config.theme.is_unicode();
+    let items = vec![
+      Msg::OptionUnicode(unicode).text(lang),
+      Msg::OptionSquare(config.theme.square).text(lang),
+      Msg::OptionAccessible(config.accessible).text(lang),
+      Msg::OptionLanguage(lang).text(lang),
+      Msg::OptionDifficulty(config.difficulty).text(lang),
+      Msg::Back.text(lang),
+    ];
+    let mut menu = Menu::new(&Msg::Options.text(lang), items);
+    menu.selected = selected;
+    menu.message = message.take();
+    selected = match menu.choose(term, lang) {
+      Some(selected) => selected,
+      None => return,
+    };
+    match selected {
+      0 => {
+        let square = config.theme.square;
+        config.theme = if unicode { Theme::ascii() } else { Theme::unicode_for_locale() };
+        config.theme.square = square;
+      },
+      // fieldの横幅は変えず、描画する列数だけを変える(収まらない分は表示範囲をスクロールする)
+      1 => { config.theme.square = !config.theme.square; },
+      2 => { config.accessible = !config.accessible; },
+      3 => { config.lang = if lang == Lang::En { Lang::Ja } else { Lang::En }; },
+      4 => {
+        // 指定したfieldにrobotが収まらない難易度は飛ばして理由を表示する
+        let current = config.difficulty;
+        let mut difficulty = current.next();
+        while difficulty != current {
+          match cli::check_robots(&Config { difficulty, ..config.clone() }) {
+            Ok(()) => break,
+            Err(e) => {
+              message.get_or_insert(Msg::CliError(&e).text(lang));
+              difficulty = difficulty.next();
+            },
+          }
+        }
+        config.difficulty = difficulty;
+      },
+      _ => return,
+    }
+  }
+}
+
+/// スコアファイルの記録を高い順に表示し、キー入力を待つ
+/// * `term` - 描画と入力に使う端末
+/// * `score_file` - スコアファイル名
+/// * `lang` - 表示する言語
+fn show_high_scores(term: &mut dyn Terminal, score_file: &str, lang: Lang) {
+  let scores = get_highscores(&get_score_file(score_file));
+  term.clear();
+  term.mv(1, 2);
+  term.addstr(&Msg::HighScoresTitle.text(lang));
+  for (i, (score, difficulty)) in scores.iter().flatten().take(HIGH_SCORES_SHOWN).enumerate() {
+    term.mv(3 + i as i32, 2);
+    let difficulty = difficulty.map_or("-".to_string(), |d| Msg::Difficulty(d).text(lang));
+    term.addstr(&format!("{:>3}. {:>10}  {}", i + 1, score, difficulty));
+  }
+  let shown = match &scores {
+    Some(scores) if !scores.is_empty() => std::cmp::min(scores.len(), HIGH_SCORES_SHOWN),
+    Some(_) => {
+      term.mv(3, 2);
+      term.addstr(&Msg::NoRecords.text(lang));
+      1
+    },
+    None => {
+      term.mv(3, 2);
+      term.addstr(&Msg::ScoreFileCorrupt.text(lang));
+      1
+    },
+  };
+  term.mv(4 + shown as i32, 2);
+  term.addstr(&Msg::PressAnyKeyToReturn.text(lang));
+  while term.getch() == ERR {}
+}
+
+/// ゲームを一時停止してメニューを表示する
+/// 選ばれた項目に対応するキーを返し、ゲームに戻る場合は`None`を返す
+/// * `term` - 描画と入力に使う端末
+/// * `lang` - 表示する言語
+fn pause_menu(term: &mut dyn Terminal, lang: Lang) -> Option<i32> {
+  let items = [Msg::Resume, Msg::Help, Msg::Statistics, Msg::Achievements, Msg::Quit];
+  let keys = [None, Some(KEY_HELP), Some(KEY_STATS), Some(KEY_ACHIEVEMENTS), Some(KEY_QUIT)];
+  Menu::new(&Msg::Paused.text(lang), items.iter().map(|item| item.text(lang)).collect())
+    .choose(term, lang).and_then(|idx| keys[idx])
+}
+
+/// 他のプレイヤーのゲームを観戦します
+/// `KEY_QUIT`が押されるかゲームが終了するまで続けます
+/// * `term` - 描画と入力に使う端末
+/// * `config` - ゲームの設定
+/// * `addr` - 観戦するゲームのアドレス
+fn watch(term: &mut dyn Terminal, config: &Config, addr: &str) {
+  let (theme, lang) = (&config.theme, config.lang);
+  let mut reader = match spectate::connect(addr) {
+    Ok(reader) => reader,
+    Err(e) => {
+      print_result!(term, &Msg::WatchFailed(addr, &e).text(lang));
+      term.getch();
+      return;
+    },
+  };
+  // フレームは別スレッドで受信し、入力を待たずに描画する
+  let (tx, rx) = std::sync::mpsc::channel();
+  std::thread::spawn(move || {
+    while let Some(frame) = spectate::read_frame(&mut reader, FIELD_POS) {
+      if tx.send(frame).is_err() {
+        break;
+      }
+    }
+  });
+
+  let (scr_w, scr_h) = term.size();
+  term.timeout(PEER_POLL_MS);
+  term.clear();
+  print_result!(term, &Msg::Watching(addr).text(lang));
+  loop {
+    if term.getch() == KEY_QUIT {
+      return;
+    }
+    // 最新のフレームだけを描画する
+    let mut latest = None;
+    loop {
+      match rx.try_recv() {
+        Ok(frame) => { latest = Some(frame); },
+        Err(std::sync::mpsc::TryRecvError::Empty) => break,
+        Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+          term.timeout(-1);
+          print_result!(term, &Msg::GameEnded.text(lang));
+          term.getch();
+          return;
+        },
+      }
+    }
+    if let Some(frame) = latest {
+      let (mut view, minimap) = layout(&frame.field, scr_w, scr_h, theme.cell_width());
+      view.follow(camera_target(&frame.field), frame.field.width, frame.field.height);
+      print_status!(term, lang, frame.level, frame.score);
+      print_field(term, theme, &frame.field, &view, &minimap);
+    }
+  }
+}
+
+/// パズルモードを開始し、全てのパズルを遊ぶか終了するまで続けます
+/// パズルモードではランダム移動は使えません
+/// * `term` - 描画と入力に使う端末
+/// * `config` - ゲームの設定
+/// * `start` - 最初に遊ぶパズルの番号(0始まり)
+fn play_puzzles(term: &mut dyn Terminal, config: &Config, start: usize) {
+  let puzzles = puzzle::puzzles(FIELD_POS);
+  let (theme, lang) = (&config.theme, config.lang);
+  let (scr_w, scr_h) = term.size();
+  // アクセシブルモードではfieldの下に説明の行を空ける
+  let scr_h = if config.accessible { scr_h - SUMMARY_LINES } else { scr_h };
+  let mut rng = rand::thread_rng();
+
+  let mut idx = start;
+  while let Some(puzzle) = puzzles.get(idx) {
+    let mut field = puzzle.field.clone();
+    let (view, minimap) = layout(&field, scr_w, scr_h, theme.cell_width());
+    term.clear();
+    print_field(term, theme, &field, &view, &minimap);
+    print_result!(term, &Msg::PuzzleTitle(idx + 1, puzzles.len(), &puzzle.name).text(lang));
+
+    let mut turns = 0;
+    let mut stop = false;
+    let solved = loop {
+      term.mv(3, 0);
+      term.addstr(&Msg::Moves(turns, puzzle.par).text(lang));
+      term.clrtoeol();
+      if config.accessible {
+        print_summary(term, &field, &view, 0, lang);
+      }
+      if !stop {
+        let mut x = field.player_pos.x;
+        let mut y = field.player_pos.y;
+        let ch = term.getch();
+        if ch == KEY_HELP {
+          show_help(term, &KeyMap::player1(), theme, lang);
+          term.clear();
+          print_field(term, theme, &field, &view, &minimap);
+          print_result!(term, &Msg::PuzzleTitle(idx + 1, puzzles.len(), &puzzle.name).text(lang));
+          continue;
+        }
+        if ch == KEY_RAND {
+          print_result!(term, &Msg::TeleportDisabled.text(lang));
+          continue;
+        }
+        match input(ch, &field, &mut x, &mut y, &mut rng) {
+          Status::Normal => (),
+          Status::Unknown => { continue; },
+          Status::Exit => { return; },
+          Status::Stop => { stop = true; },
+        }
+        if !field.player_move(Point::new(x, y)) {
+          continue;
+        }
+      }
+
+      turns += 1;
+      let robot_res = field.robots_move(stop);
+      print_field(term, theme, &field, &view, &minimap);
+      if robot_res.is_none() {
+        break false;
+      }
+      if field.all_robots_destroyed() {
+        break true;
+      }
+    };
+
+    term.mv(3, 0);
+    term.addstr(&Msg::Moves(turns, puzzle.par).text(lang));
+    term.clrtoeol();
+    if solved {
+      print_result!(term, &Msg::Solved(turns, turns.cmp(&puzzle.par)).text(lang));
+    } else {
+      print_result!(term, &Msg::PuzzleFailed(turns).text(lang));
+    }
+    loop {
+      match term.getch() {
+        KEY_RETRY => break,
+        KEY_NEXT => { idx += 1; break; },
+        KEY_QUIT => { return; },
+        _ => (),
+      }
+    }
+  }
+  print_result!(term, &format!("{} {}", Msg::AllPuzzlesPlayed.text(lang), Msg::PressAnyKey.text(lang)));
+  term.getch();
+}
+
+/// ゲームを開始し、playerが負けるか終了するまで続けます
+/// 対戦相手がいる場合は先に負けた方が敗者となります
+/// 今回のゲームの記録を返します
+/// * `term` - 描画と入力に使う端末
+/// * `config` - ゲームの設定
+/// * `resumed` - 再開するゲームの状態
+/// * `peer` - 対戦相手との接続
+/// * `spectators` - 観戦者への配信
+fn play(term: &mut dyn Terminal, config: &Config, resumed: Option<SaveData>, mut peer: Option<Peer>, spectators: &mut Option<Broadcaster>) -> Stats {
+  // デイリーモードはメニューでの設定の変更に関わらず全員が同じ設定で遊ぶ
+  let daily_config = config.daily.map(|_| config.for_daily());
+  let config = daily_config.as_ref().unwrap_or(config);
+  let lang = config.lang;
+  let mut session = Stats::default();
+  let mut achievements = Achievements::load(ACHIEVEMENTS_FILE, &config.profile);
+  let (mut level, mut score, mut scoring, mut rules, mut rng, mut resumed) = match resumed {
+    Some(data) => (data.level, data.score, data.scoring, data.rules, data.rng, Some(data.field)),
+    None => {
+      let scoring = ScoreEngine::new(config.difficulty.preset().score_percent);
+      let rng = match config.daily {
+        // デイリーモードではランダム移動も全員で同じ乱数を使う
+        Some(day) => daily::rng(day, 0),
+        None => config.seed.map_or_else(ChaCha8Rng::from_entropy, ChaCha8Rng::seed_from_u64),
+      };
+      (config.start_level, 0, scoring, Rules::new(config.difficulty), rng, None)
+    },
+  };
+  let preset = rules.difficulty.preset();
+
+  // デイリーモードでランキングの対象になるのは1日1回まで
+  let mut ranked = false;
+  if let Some(day) = config.daily {
+    let date = daily::date_string(day);
+    let mut table = DailyTable::load(DAILY_FILE);
+    ranked = !table.has_played(&date, &config.profile);
+    if ranked {
+      // 途中で終了しても挑戦したことを記録する
+      table.record(&date, &config.profile, 0);
+      if table.save(DAILY_FILE).is_err() {
+        print_result!(term, &Msg::DailySaveFailed.text(lang));
+        term.getch();
+      }
+    } else {
+      print_result!(term, &format!("{} {}", Msg::AlreadyPlayedDaily(&date).text(lang), Msg::PressAnyKey.text(lang)));
+      term.getch();
+    }
+  }
+
+  let (scr_w, scr_h) = term.size();
+  // アクセシブルモードではfieldの下に説明の行を空ける
+  let scr_h = if config.accessible { scr_h - SUMMARY_LINES } else { scr_h };
+
+  // 対戦中は相手の状態を表示するため入力を待ち続けない
+  if peer.is_some() {
+    term.timeout(PEER_POLL_MS);
+  }
+  let keymaps = [KeyMap::player1(), KeyMap::player2()];
+
+  loop {
+    let robots_num = config.robots(level);
+    let generated = match resumed.take() {
+      // 再開したlevelは連続得点などの記録を引き継ぐ
+      Some(field) => Ok(field),
+      None => {
+        scoring.start_level();
+        rules.start_level();
+        let (width, height) = (config.width, config.height);
+        let placement = config.placement(rules.difficulty, level);
+        match (&peer, config.daily) {
+          // 対戦相手と同じfieldを生成する
+          (Some(peer), _) => Field::try_with_placement(FIELD_POS, width, height, robots_num, &mut peer.level_rng(level), false, &placement),
+          // その日の全員と同じfieldを生成する
+          (None, Some(day)) => Field::try_with_placement(FIELD_POS, width, height, robots_num, &mut daily::rng(day, level), false, &placement),
+          (None, None) => Field::try_with_placement(FIELD_POS, width, height, robots_num, &mut rng, config.coop, &placement),
+        }
+      },
+    };
+    let mut field = match generated {
+      Ok(field) => field,
+      Err(e) => {
+        term.timeout(-1);
+        print_result!(term, &format!("{} {}", Msg::CannotCreateField(level, &e).text(lang), Msg::PressAnyKey.text(lang)));
+        term.getch();
+        return session;
+      },
+    };
+    let (mut view, minimap) = layout(&field, scr_w, scr_h, config.theme.cell_width());
+    term.clear();
+    print_field(term, &config.theme, &field, &view, &minimap);
+    if let Some(spectators) = spectators.as_mut() {
+      spectators.publish(level, score, &field);
+    }
+    unlock(term, &mut achievements, &Event::LevelStarted(level), lang);
 
-    let mut x = field.player_pos.x;
-    let mut y = field.player_pos.y;
     let mut robot_res;
     let mut stop = false;
+    // 止まるモードで生き残ったターン数
+    let mut stop_turns = 0;
+    session.highest_level = std::cmp::max(session.highest_level, level);
+    // 入力するplayerの番号
+    let mut who = 0;
+    // リアルタイムモードでrobotが動く間隔と次に動く時刻(対戦中と協力プレイ中は使わない)
+    let tick = config.tick_ms(level).filter(|_| peer.is_none() && !config.coop).map(Duration::from_millis);
+    let mut next_tick = tick.map(|tick| Instant::now() + tick);
 
-    print_result!("         ");
+    print_result!(term, "");
     loop {
-      print_status!(level, score);
+      print_status!(term, lang, level, score);
+      if let Some(left) = rules.teleports_left {
+        term.addstr(&Msg::TeleportsLeft(left).text(lang));
+      }
+      if let Some(peer) = &peer {
+        let state = peer.state();
+        print_peer_status(term, &state, lang);
+        if !state.alive {
+          term.timeout(-1);
+          print_result!(term, &Msg::OpponentDown.text(lang));
+          record_score(term, &config.score_file, score, &scoring.totals, rules.difficulty, lang);
+          term.getch();
+          session.games_played = 1;
+          return session;
+        }
+      }
+      if config.coop {
+        print_turn(term, who, lang);
+      }
+      if config.accessible {
+        print_summary(term, &field, &view, who, lang);
+      }
+      // リアルタイムモードでは入力が無くても時間になればrobotが動く
+      let mut ticked = false;
+      if let Some(deadline) = next_tick {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if stop {
+          std::thread::sleep(remaining);
+          ticked = true;
+        } else if remaining.is_zero() {
+          ticked = true;
+        } else {
+          term.timeout(remaining.as_millis() as i32);
+        }
+      }
       // 入力
-      if !stop {
-        match input(getch(), &field, &mut x, &mut y) {
+      if !stop && !ticked {
+        let pos = field.player(who).unwrap_or(field.player_pos);
+        let mut x = pos.x;
+        let mut y = pos.y;
+        let mut ch = term.getch();
+        if ch == KEY_PAUSE {
+          let key = pause_menu(term, lang);
+          term.clear();
+          print_field(term, &config.theme, &field, &view, &minimap);
+          // 一時停止している間はリアルタイムモードの時間を進めない
+          if let (Some(deadline), Some(tick)) = (&mut next_tick, tick) {
+            *deadline = Instant::now() + tick;
+          }
+          match key {
+            Some(key) => { ch = key; },
+            None => { continue; },
+          }
+        }
+        if ch == KEY_STATS {
+          let mut lifetime = stats::load(STATS_FILE);
+          lifetime.merge(&session);
+          show_stats(term, &lifetime, lang);
+          term.clear();
+          print_field(term, &config.theme, &field, &view, &minimap);
+          continue;
+        }
+        if ch == KEY_ACHIEVEMENTS {
+          show_achievements(term, &achievements, lang);
+          term.clear();
+          print_field(term, &config.theme, &field, &view, &minimap);
+          continue;
+        }
+        if ch == KEY_HELP {
+          show_help(term, &keymaps[who], &config.theme, lang);
+          term.clear();
+          print_field(term, &config.theme, &field, &view, &minimap);
+          continue;
+        }
+        let action = keymaps[who].translate(ch);
+        if action == KEY_RAND && !rules.can_teleport() {
+          print_result!(term, &Msg::NoTeleportsLeft.text(lang));
+          continue;
+        }
+        match input(action, &field, &mut x, &mut y, &mut rng) {
           Status::Normal => (),
           Status::Unknown => { continue; },
-          Status::Exit => { exit!(); },
+          // 対戦中と協力プレイ中とデイリーモードでは保存できない
+          Status::Exit if peer.is_some() || config.coop || config.daily.is_some() => {
+            if let Some(peer) = &mut peer {
+              let _ = peer.send(level, score, false);
+            }
+            if let Some(day) = config.daily {
+              record_daily(term, day, &config.profile, score, ranked, lang);
+            }
+            session.games_played = 1;
+            return session;
+          },
+          Status::Exit => {
+            term.timeout(-1);
+            print_result!(term, &Msg::SavePrompt.text(lang));
+            match term.getch() {
+              KEY_YES => {
+                match save::save_game(SAVE_FILE, level, score, &scoring, &rules, &rng, &field) {
+                  Ok(_) => { return session; },
+                  Err(_) => { print_result!(term, &Msg::SaveFailed.text(lang)); },
+                }
+              },
+              KEY_NO => {
+                session.games_played = 1;
+                return session;
+              },
+              _ => { print_result!(term, ""); },
+            }
+            continue;
+          },
           Status::Stop => { stop = true; },
         }
-      }
 
-      // プレイヤーの移動
-      if !field.player_move(Point::new(x, y)) {
-        x = field.player_pos.x;
-        y = field.player_pos.y;
-        continue;
+        // 倒される移動は、他に生き残れる移動がある場合だけ止める
+        if preset.safe_moves && !stop && action != KEY_RAND &&
+          !field.is_safe_move(who, Point::new(x, y)) && field.has_safe_move(who) {
+          print_result!(term, &Msg::UnsafeMove.text(lang));
+          continue;
+        }
+        // プレイヤーの移動
+        if !field.move_player(who, Point::new(x, y)) {
+          continue;
+        }
+        if action == KEY_RAND {
+          rules.teleport();
+          scoring.teleport();
+          session.teleports += 1;
+        }
+        // 協力プレイでは2人とも入力してからrobotを動かす
+        if who == 0 && !stop && field.player(1).is_some() {
+          who = 1;
+          print_field(term, &config.theme, &field, &view, &minimap);
+          continue;
+        }
+        // リアルタイムモードではplayerの移動でrobotは動かない
+        if next_tick.is_some() {
+          view.follow(camera_target(&field), field.width, field.height);
+          print_field(term, &config.theme, &field, &view, &minimap);
+          continue;
+        }
+      }
+      if let (Some(deadline), Some(tick)) = (&mut next_tick, tick) {
+        *deadline = Instant::now() + tick;
       }
 
       // 勝ち負けを判定
+      let alive = [field.player_alive, field.partner_alive];
       robot_res = field.robots_move(stop);
-      field.print();
+      session.record_turn(&field.report);
+      if stop && robot_res.is_some() {
+        stop_turns += 1;
+        session.longest_stop_streak = std::cmp::max(session.longest_stop_streak, stop_turns);
+      }
+      if let Some(scr) = robot_res {
+        let turn = scoring.score_turn(scr, &field.report);
+        score += turn.total();
+        print_breakdown(term, &turn, lang);
+        unlock(term, &mut achievements, &Event::Turn { report: field.report, stop }, lang);
+      }
+      view.follow(camera_target(&field), field.width, field.height);
+      print_field(term, &config.theme, &field, &view, &minimap);
+      if let Some(spectators) = spectators.as_mut() {
+        spectators.publish(level, score, &field);
+      }
+      who = if field.player(0).is_some() { 0 } else { 1 };
 
       match robot_res {
-        Some(scr) => { score += scr },
+        Some(_) => {
+          if alive[0] && !field.player_alive {
+            print_result!(term, &Msg::PlayerDown(0).text(lang));
+          } else if alive[1] && !field.partner_alive {
+            print_result!(term, &Msg::PlayerDown(1).text(lang));
+          }
+        },
         _ => {
-          print_result!("you lose");
-          let highscore = get_highscore(&get_score_file("score.dat"));
-          if score > highscore {
-            mv(2, 0);
-            addstr(&format!("HighScore!! {} points", score));
-            save_record("score.dat", score);
+          term.timeout(-1);
+          match &mut peer {
+            Some(peer) => {
+              let _ = peer.send(level, score, false);
+              if peer.state().alive {
+                print_result!(term, &Msg::YouLoseToOpponent.text(lang));
+              } else {
+                print_result!(term, &Msg::OpponentWentDownFirst.text(lang));
+              }
+            },
+            None => { print_result!(term, &Msg::YouLose.text(lang)); },
           }
-          getch();
-          exit!();
+          if config.daily.is_none() {
+            record_score(term, &config.score_file, score, &scoring.totals, rules.difficulty, lang);
+          }
+          term.getch();
+          if let Some(day) = config.daily {
+            record_daily(term, day, &config.profile, score, ranked, lang);
+          }
+          session.games_played = 1;
+          return session;
         }
       }
-      if field.robots_pos.len() == 0 {
-        print_result!("you win");
-        print_status!(level, score);
+      if let Some(peer) = &mut peer {
+        let _ = peer.send(level, score, true);
+      }
+      if field.all_robots_destroyed() {
+        print_result!(term, &Msg::YouWin.text(lang));
+        print_status!(term, lang, level, score);
 
-        score += (level * 10) as u64;
-        getch();
+        score += scoring.clear_level(level);
+        unlock(term, &mut achievements, &Event::LevelCleared { level, teleported: scoring.teleported, stop }, lang);
+        // 対戦中は相手の状態を表示しながら入力を待つ
+        while term.getch() == ERR {
+          if let Some(peer) = &peer {
+            print_peer_status(term, &peer.state(), lang);
+          }
+        }
         break;
       }
     }
@@ -108,29 +912,238 @@ fn main() {
   }
 }
 
+/// 累計の記録を表示し、キー入力を待つ
+/// * `term` - 描画と入力に使う端末
+/// * `stats` - 表示する記録
+/// * `lang` - 表示する言語
+fn show_stats(term: &mut dyn Terminal, stats: &Stats, lang: Lang) {
+  term.clear();
+  term.mv(1, 2);
+  term.addstr(&Msg::StatisticsTitle.text(lang));
+  for (i, (stat, value)) in stats.rows().iter().enumerate() {
+    term.mv(3 + i as i32, 2);
+    term.addstr(&format!("{}{:>10}", i18n::pad(&Msg::Stat(*stat).text(lang), 24), value));
+  }
+  term.mv(4 + stats.rows().len() as i32, 2);
+  term.addstr(&Msg::PressAnyKeyToReturn.text(lang));
+  while term.getch() == ERR {}
+}
+
+/// 出来事から実績を判定し、新しく獲得した実績を表示して保存する
+/// * `term` - 描画と入力に使う端末
+/// * `achievements` - 獲得した実績
+/// * `event` - ゲーム中の出来事
+/// * `lang` - 表示する言語
+fn unlock(term: &mut dyn Terminal, achievements: &mut Achievements, event: &Event, lang: Lang) {
+  let new = achievements.notify(event);
+  if new.is_empty() {
+    return;
+  }
+  let titles: Vec<String> = new.iter().map(|a| Msg::AchievementTitle(*a).text(lang)).collect();
+  term.mv(0, 0);
+  term.addstr(&Msg::AchievementUnlocked(&titles.join(", ")).text(lang));
+  term.clrtoeol();
+  if achievements.save(ACHIEVEMENTS_FILE).is_err() {
+    print_result!(term, &Msg::AchievementsSaveFailed.text(lang));
+  }
+}
+
+/// 実績の一覧を表示し、キー入力を待つ
+/// * `term` - 描画と入力に使う端末
+/// * `achievements` - 獲得した実績
+/// * `lang` - 表示する言語
+fn show_achievements(term: &mut dyn Terminal, achievements: &Achievements, lang: Lang) {
+  term.clear();
+  term.mv(1, 2);
+  term.addstr(&Msg::AchievementsTitle(&achievements.profile).text(lang));
+  for (i, a) in Achievement::ALL.iter().enumerate() {
+    let mark = if achievements.is_unlocked(*a) { 'x' } else { ' ' };
+    term.mv(3 + i as i32, 2);
+    term.addstr(&format!("[{}] {}{}", mark, i18n::pad(&Msg::AchievementTitle(*a).text(lang), 20),
+      Msg::AchievementDescription(*a).text(lang)));
+  }
+  term.mv(4 + Achievement::ALL.len() as i32, 2);
+  term.addstr(&Msg::PressAnyKeyToReturn.text(lang));
+  while term.getch() == ERR {}
+}
+
+/// 操作方法と記号の凡例を表示し、キー入力を待つ
+/// * `term` - 描画と入力に使う端末
+/// * `keymap` - 入力中のplayerのキー配置
+/// * `theme` - fieldの描画に使う記号
+/// * `lang` - 表示する言語
+fn show_help(term: &mut dyn Terminal, keymap: &KeyMap, theme: &Theme, lang: Lang) {
+  term.clear();
+  term.mv(1, 2);
+  term.addstr(&Msg::HelpTitle.text(lang));
+  let lines = help::lines(keymap, theme, lang);
+  for (i, line) in lines.iter().enumerate() {
+    term.mv(3 + i as i32, 2);
+    term.addstr(line);
+  }
+  term.mv(4 + lines.len() as i32, 2);
+  term.addstr(&Msg::HelpExtraKeys.text(lang));
+  term.mv(6 + lines.len() as i32, 2);
+  term.addstr(&Msg::PressAnyKeyToReturn.text(lang));
+  while term.getch() == ERR {}
+}
+
+/// 協力プレイで入力するplayerを表示する
+/// * `term` - 描画と入力に使う端末
+/// * `who` - 入力するplayerの番号
+/// * `lang` - 表示する言語
+fn print_turn(term: &mut dyn Terminal, who: usize, lang: Lang) {
+  term.mv(3, 40);
+  let glyph = if who == 0 { Object::Player.glyph() } else { Object::Partner.glyph() };
+  term.addstr(&Msg::PlayerToMove(who, glyph).text(lang));
+  term.clrtoeol();
+}
+
+/// 表示範囲が追いかける座標を返します
+/// 生き残っているplayerが複数いる場合はその中間の座標を返します
+/// * `field` - 表示するfield
+fn camera_target(field: &Field) -> Point {
+  let players = field.living_players();
+  if players.is_empty() {
+    return field.player_pos;
+  }
+  let n = players.len();
+  Point::new(players.iter().map(|p| p.x).sum::<usize>() / n, players.iter().map(|p| p.y).sum::<usize>() / n)
+}
+
+/// 対戦相手の状態を表示する
+/// * `term` - 描画と入力に使う端末
+/// * `state` - 対戦相手の状態
+/// * `lang` - 表示する言語
+fn print_peer_status(term: &mut dyn Terminal, state: &PeerState, lang: Lang) {
+  term.mv(3, 40);
+  if state.connected {
+    term.addstr(&Msg::OpponentStatus(state.level, state.score).text(lang));
+  } else {
+    term.addstr(&Msg::OpponentDisconnected.text(lang));
+  }
+  term.clrtoeol();
+}
+
+/// 直前のターンで獲得したscoreの内訳を表示する
+/// * `term` - 描画と入力に使う端末
+/// * `turn` - 直前のターンの内訳
+/// * `lang` - 表示する言語
+fn print_breakdown(term: &mut dyn Terminal, turn: &ScoreBreakdown, lang: Lang) {
+  term.mv(2, 0);
+  if turn.total() > 0 {
+    term.addstr(&Msg::Breakdown(turn).text(lang));
+  }
+  term.clrtoeol();
+}
+
+/// ハイスコアを更新した場合は表示してスコアファイルに記録する
+/// スコアファイルの形式が違う場合は上書きせずにエラーを表示する
+/// * `term` - 描画と入力に使う端末
+/// * `score_file` - スコアファイル名
+/// * `score` - 最終的なscore
+/// * `breakdown` - 最終的なscoreの内訳
+/// * `difficulty` - 遊んだ難易度
+/// * `lang` - 表示する言語
+fn record_score(term: &mut dyn Terminal, score_file: &str, score: u64, breakdown: &ScoreBreakdown, difficulty: Difficulty, lang: Lang) {
+  term.mv(2, 0);
+  match get_highscore(&get_score_file(score_file)) {
+    Some(highscore) if score > highscore => {
+      match save_record(score_file, score, breakdown, difficulty) {
+        Ok(_) => term.addstr(&Msg::HighScore(score).text(lang)),
+        Err(e) => term.addstr(&Msg::ScoreSaveFailed(&e).text(lang)),
+      };
+    },
+    Some(_) => (),
+    None => { term.addstr(&Msg::ScoreFileCorrupt.text(lang)); },
+  }
+  term.clrtoeol();
+}
+
+/// デイリーモードのscoreをハイスコア表に記録し、その日のランキングを表示する
+/// ランキングの対象でない場合は記録しない
+/// * `term` - 描画と入力に使う端末
+/// * `day` - 1970-01-01からの日数
+/// * `profile` - プロフィール名
+/// * `score` - 最終的なscore
+/// * `ranked` - ランキングの対象か
+/// * `lang` - 表示する言語
+fn record_daily(term: &mut dyn Terminal, day: u64, profile: &str, score: u64, ranked: bool, lang: Lang) {
+  let date = daily::date_string(day);
+  let mut table = DailyTable::load(DAILY_FILE);
+  let saved = !ranked || {
+    table.record(&date, profile, score);
+    table.save(DAILY_FILE).is_ok()
+  };
+
+  term.clear();
+  term.mv(1, 2);
+  term.addstr(&format!("*** {} ***", Msg::DailyRanking(&date).text(lang)));
+  let ranking = table.ranking(&date);
+  for (i, entry) in ranking.iter().take(10).enumerate() {
+    term.mv(3 + i as i32, 2);
+    term.addstr(&format!("{:>3}. {:<20}{:>10}", i + 1, entry.profile, entry.score));
+  }
+  term.mv(4 + std::cmp::min(ranking.len(), 10) as i32, 2);
+  let message = if saved { Msg::YourScore(score, ranked) } else { Msg::DailySaveFailed };
+  term.addstr(&format!("{} {}", message.text(lang), Msg::PressAnyKey.text(lang)));
+  while term.getch() == ERR {}
+}
+
+/// fieldとミニマップを描画する
+/// * `term` - 描画と入力に使う端末
+/// * `theme` - fieldの描画に使う記号
+/// * `field` - 描画するfield
+/// * `view` - fieldの表示範囲
+/// * `minimap` - ミニマップの配置
+fn print_field(term: &mut dyn Terminal, theme: &Theme, field: &Field, view: &Viewport, minimap: &Option<Minimap>) {
+  field.print(term, view, theme);
+  if let Some(minimap) = minimap {
+    minimap.print(term, field, view, theme);
+  }
+}
+
+/// アクセシブルモードでfieldの状況を説明する文章をfieldの下に表示する
+/// * `term` - 描画と入力に使う端末
+/// * `field` - 説明するfield
+/// * `view` - fieldの表示範囲
+/// * `who` - 入力するplayerの番号
+/// * `lang` - 表示する言語
+fn print_summary(term: &mut dyn Terminal, field: &Field, view: &Viewport, who: usize, lang: Lang) {
+  let y = (field.pos.y + view.height + 1) as i32;
+  let lines = accessible::summary(field, who, lang);
+  for i in 0..SUMMARY_LINES {
+    term.mv(y + i, 0);
+    if let Some(line) = lines.get(i as usize) {
+      term.addstr(line);
+    }
+    term.clrtoeol();
+  }
+}
+
 /// 8方向+その場にとどまる+ランダム移動+終了を入力する
 /// 終了の場合はfalseを返し、それ以外はtrueを返す
 /// * `ch` - キー入力
 /// * `field` - フィールドの情報
 /// * `x_org` - 現在のプレイヤーのx座標
 /// * `y_org` - 現在のプレイヤーのy座標
-fn input(ch: i32, field: &Field, x_org: &mut usize, y_org: &mut usize) -> Status {
-  let mut rng = rand::thread_rng();
+/// * `rng` - ランダム移動に使う乱数生成器
+fn input<R: Rng>(ch: i32, field: &Field, x_org: &mut usize, y_org: &mut usize, rng: &mut R) -> Status {
   let mut x = *x_org;
   let mut y = *y_org;
 
   match ch {
     KEY_RIGHT => { if x < field.width-1 { x += 1; } },
-    KEY_LEFT  => { if x > 0 { x -= 1; } },
+    KEY_LEFT  => { x = x.saturating_sub(1); },
     KEY_DOWN  => { if y < field.height-1 { y += 1; } },
-    KEY_UP    => { if y > 0 { y -= 1; } },
+    KEY_UP    => { y = y.saturating_sub(1); },
     KEY_RUP   => { 
-      if y > 0 { y -= 1; }
+      y = y.saturating_sub(1);
       if x < field.width-1 { x += 1; }
     },
     KEY_LUP   => {
-      if y > 0 { y -= 1; }
-      if x > 0 { x -= 1; }
+      y = y.saturating_sub(1);
+      x = x.saturating_sub(1);
     },
     KEY_RDOWN => {
       if y < field.height-1 { y += 1; } 
@@ -138,7 +1151,7 @@ fn input(ch: i32, field: &Field, x_org: &mut usize, y_org: &mut usize) -> Status
     },
     KEY_LDOWN => {
       if y < field.height-1 { y += 1; } 
-      if x > 0 { x -= 1; }
+      x = x.saturating_sub(1);
     },
     KEY_RAND  => {
       x = rng.gen::<usize>() % field.width;
@@ -157,40 +1170,65 @@ fn input(ch: i32, field: &Field, x_org: &mut usize, y_org: &mut usize) -> Status
 /// 記録をファイルから読み込み、文字列として返します
 /// * `filename` - 記録のファイル名
 fn get_score_file(filename: &str) -> String {
-  match fs::read_to_string(&filename) {
+  match fs::read_to_string(filename) {
     Ok(val) => val.trim().to_string(),
     _ => "".to_string(),
   }
 }
 
 /// 最も高いスコアを返します
+/// 各行の先頭の数値をスコアとして扱います
+/// 形式が違う場合は`None`を返します
 /// * `score_file` - 記録ファイルの文字列データ
-fn get_highscore(file_str: &str) -> u64 {
-  if file_str != "" {
-    let score_str: Vec<&str> = file_str.split('\n').collect::<Vec<&str>>();
-    let mut scores: Vec<u64> = score_str.iter().map(|x| x.parse()
-      .expect("スコアファイルの形式が違います")).collect();
-    scores.sort();
-    scores[scores.len()-1]
-  } else {
-    0
+fn get_highscore(file_str: &str) -> Option<u64> {
+  Some(get_highscores(file_str)?.first().map_or(0, |(score, _)| *score))
+}
+
+/// 記録されたスコアと難易度を高い順に返します
+/// 各行の先頭の数値をスコア、内訳の後の単語を難易度として扱います
+/// 難易度が記録されていない行の難易度は`None`になります
+/// 形式が違う場合は`None`を返します
+/// * `score_file` - 記録ファイルの文字列データ
+fn get_highscores(file_str: &str) -> Option<Vec<(u64, Option<Difficulty>)>> {
+  if file_str.is_empty() {
+    return Some(Vec::new());
   }
+  let mut scores: Vec<(u64, Option<Difficulty>)> = file_str.split('\n')
+    .map(|x| {
+      let mut words = x.split_whitespace();
+      let score = words.next().unwrap_or("").parse().ok()?;
+      Some((score, words.nth(6).and_then(Difficulty::parse)))
+    })
+    .collect::<Option<Vec<(u64, Option<Difficulty>)>>>()?;
+  scores.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+  Some(scores)
 }
 
 /// 記録をスコアファイルに追記します
 /// * `filename` - 保存するファイル名
 /// * `score` - 保存するスコア
-fn save_record(filename: &str, score: u64) {
+/// * `breakdown` - スコアの内訳
+/// * `difficulty` - 遊んだ難易度
+fn save_record(filename: &str, score: u64, breakdown: &ScoreBreakdown, difficulty: Difficulty) -> std::io::Result<()> {
   let score_file = get_score_file(filename);
-  let new_score_file = format!("{}\n{}", score_file, score);
-  fs::write(&filename, new_score_file.trim())
-    .expect("スコアデータの書き込みに失敗しました");
+  let new_score_file = format!("{}\n{} {} {}", score_file, score, breakdown.encode(), difficulty.name());
+  fs::write(filename, new_score_file.trim())
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+#[test]
+  fn get_highscore_test() {
+    assert_eq!(get_highscore(""), Some(0));
+    assert_eq!(get_highscore("10\n30\n20"), Some(30));
+    assert_eq!(get_highscore("10\n42 30 1 1 0 0 10\n20"), Some(42));
+    assert_eq!(get_highscore("10\nbroken"), None);
+    assert_eq!(get_highscores("10\n42 30 1 1 0 0 10 hard\n20 5 0 0 0 0 10 unknown"),
+      Some(vec![(42, Some(Difficulty::Hard)), (20, None), (10, None)]));
+  }
+
 #[test]
   fn input_test_up() {
     let mut field = Field::new(Point::new(0, 0), 50, 20, 0);
@@ -198,43 +1236,43 @@ mod tests {
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_LUP as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_LUP, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 0));
 
-    assert_eq!(input(KEY_UP as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_UP, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 0));
 
-    assert_eq!(input(KEY_RUP as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_RUP, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(1, 0));
 
     field.player_move(Point::new(1, 3));
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_LUP as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_LUP, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 2));
 
-    assert_eq!(input(KEY_UP as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_UP, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 1));
 
-    assert_eq!(input(KEY_RUP as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_RUP, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(1, 0));
 
     field.player_move(Point::new(0, 3));
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_LUP as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_LUP, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 2));
 
     field.player_move(Point::new(field.width-1, 3));
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_RUP as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_RUP, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(field.width-1, 2));
 
-    assert_eq!(input(KEY_QUIT as i32, &field, &mut x, &mut y), Status::Exit);
+    assert_eq!(input(KEY_QUIT, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Exit);
   }
 
 #[test]
@@ -244,27 +1282,27 @@ mod tests {
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_LEFT as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_LEFT, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 0));
 
-    assert_eq!(input(KEY_STAY as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_STAY, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 0));
 
-    assert_eq!(input(KEY_RIGHT as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_RIGHT, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(1, 0));
 
     field.player_move(Point::new(0, 3));
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_LEFT as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_LEFT, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 3));
 
     field.player_move(Point::new(field.width-1, 3));
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_RIGHT as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_RIGHT, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(field.width-1, 3));
   }
 
@@ -275,40 +1313,40 @@ mod tests {
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_LDOWN as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_LDOWN, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, field.height-1));
 
-    assert_eq!(input(KEY_DOWN as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_DOWN, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, field.height-1));
 
-    assert_eq!(input(KEY_RDOWN as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_RDOWN, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(1, field.height-1));
 
     field.player_move(Point::new(1, 3));
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_LDOWN as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_LDOWN, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 4));
 
-    assert_eq!(input(KEY_DOWN as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_DOWN, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 5));
 
-    assert_eq!(input(KEY_RDOWN as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_RDOWN, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(1, 6));
 
     field.player_move(Point::new(0, 3));
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_LDOWN as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_LDOWN, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(0, 4));
 
     field.player_move(Point::new(field.width-1, 3));
     let mut x = field.player_pos.x;
     let mut y = field.player_pos.y;
 
-    assert_eq!(input(KEY_RDOWN as i32, &field, &mut x, &mut y), Status::Normal);
+    assert_eq!(input(KEY_RDOWN, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Normal);
     assert_eq!(Point::new(x, y), Point::new(field.width-1, 4));
   }
 
@@ -325,7 +1363,7 @@ mod tests {
         KEY_RUP | KEY_RDOWN | KEY_LUP | KEY_LDOWN | KEY_RAND | KEY_STOP
         => (),
         _ => {
-          assert_eq!(input(i, &field, &mut x, &mut y), Status::Unknown);
+          assert_eq!(input(i, &field, &mut x, &mut y, &mut rand::thread_rng()), Status::Unknown);
           assert_eq!(Point::new(x,y), Point::new(5, 5)); 
         },
       }
@@ -0,0 +1,124 @@
+use crate::internal::{KEY_DOWN, KEY_QUIT, KEY_STAY, KEY_UP};
+use crate::term::Terminal;
+use crate::i18n::{Lang, Msg};
+
+/// Enterキー(改行の変換を無効にした端末で送られる値)
+const KEY_ENTER_CR: i32 = 13;
+/// Enterキー
+const KEY_ENTER_LF: i32 = 10;
+
+/// メニューでキーを押した結果を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MenuInput {
+  /// 選択中の項目が変わった
+  Moved,
+  /// 指定の番号の項目が選ばれた
+  Chosen(usize),
+  /// メニューを閉じる
+  Back,
+  /// メニューの操作に使わないキー
+  Ignored,
+}
+
+/// キーボードで項目を選ぶメニューを表します
+pub struct Menu {
+  /// メニューの見出し
+  pub title: String,
+  /// 項目のリスト
+  pub items: Vec<String>,
+  /// 選択中の項目の番号
+  pub selected: usize,
+  /// 操作方法の下に表示するメッセージ
+  pub message: Option<String>,
+}
+
+impl Menu {
+  /// 最初の項目を選択した状態のメニューを生成します
+  /// * `title` - メニューの見出し
+  /// * `items` - 項目のリスト
+  pub fn new(title: &str, items: Vec<String>) -> Menu {
+    Menu { title: title.to_string(), items, selected: 0, message: None }
+  }
+
+  /// キー入力でメニューを操作します
+  /// 上下の移動キーで項目を選び、Enterかスペースで決定します
+  /// 数字キーでは対応する番号の項目をすぐに選べます
+  /// * `ch` - 入力されたキー
+  pub fn key(&mut self, ch: i32) -> MenuInput {
+    if self.items.is_empty() {
+      return MenuInput::Back;
+    }
+    match ch {
+      KEY_UP => {
+        self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        MenuInput::Moved
+      },
+      KEY_DOWN => {
+        self.selected = (self.selected + 1) % self.items.len();
+        MenuInput::Moved
+      },
+      KEY_ENTER_CR | KEY_ENTER_LF | KEY_STAY => MenuInput::Chosen(self.selected),
+      KEY_QUIT => MenuInput::Back,
+      ch if ch >= b'1' as i32 && ch < b'1' as i32 + self.items.len() as i32 => {
+        self.selected = (ch - b'1' as i32) as usize;
+        MenuInput::Chosen(self.selected)
+      },
+      _ => MenuInput::Ignored,
+    }
+  }
+
+  /// メニューを描画します
+  /// * `term` - 描画先の端末
+  /// * `lang` - 操作方法を表示する言語
+  pub fn print(&self, term: &mut dyn Terminal, lang: Lang) {
+    term.clear();
+    term.mv(1, 2);
+    term.addstr(&format!("*** {} ***", self.title));
+    for (i, item) in self.items.iter().enumerate() {
+      let mark = if i == self.selected { '>' } else { ' ' };
+      term.mv(3 + i as i32, 2);
+      term.addstr(&format!("{} {}. {}", mark, i + 1, item));
+    }
+    term.mv(4 + self.items.len() as i32, 2);
+    term.addstr(&Msg::MenuHint.text(lang));
+    if let Some(message) = &self.message {
+      term.mv(6 + self.items.len() as i32, 2);
+      term.addstr(message);
+    }
+  }
+
+  /// メニューを表示し、項目が選ばれるまで入力を待ちます
+  /// 選ばれた項目の番号を返し、メニューを閉じた場合は`None`を返します
+  /// * `term` - 描画と入力に使う端末
+  /// * `lang` - 操作方法を表示する言語
+  pub fn choose(&mut self, term: &mut dyn Terminal, lang: Lang) -> Option<usize> {
+    self.print(term, lang);
+    loop {
+      match self.key(term.getch()) {
+        MenuInput::Moved => self.print(term, lang),
+        MenuInput::Chosen(idx) => return Some(idx),
+        MenuInput::Back => return None,
+        MenuInput::Ignored => (),
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn menu_key_test() {
+    let mut menu = Menu::new("title", vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+    assert_eq!(menu.key(KEY_UP), MenuInput::Moved);
+    assert_eq!(menu.selected, 2);
+    assert_eq!(menu.key(KEY_DOWN), MenuInput::Moved);
+    assert_eq!(menu.key(KEY_DOWN), MenuInput::Moved);
+    assert_eq!(menu.key(KEY_ENTER_CR), MenuInput::Chosen(1));
+    assert_eq!(menu.key(b'3' as i32), MenuInput::Chosen(2));
+    assert_eq!(menu.key(b'4' as i32), MenuInput::Ignored);
+    assert_eq!(menu.key(b'x' as i32), MenuInput::Ignored);
+    assert_eq!(menu.key(KEY_QUIT), MenuInput::Back);
+  }
+}
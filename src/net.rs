@@ -0,0 +1,187 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// 対戦で使うデフォルトのポート番号
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// 対戦相手の状態を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PeerState {
+  /// 相手のlevel
+  pub level: u32,
+  /// 相手のscore
+  pub score: u64,
+  /// 相手が生き残っているか
+  pub alive: bool,
+  /// 相手と接続しているか
+  pub connected: bool,
+}
+
+impl Default for PeerState {
+  fn default() -> PeerState {
+    PeerState { level: 1, score: 0, alive: true, connected: true }
+  }
+}
+
+/// 対戦相手との接続を表します
+pub struct Peer {
+  /// 相手への送信に使うストリーム
+  stream: TcpStream,
+  /// 受信した相手の状態
+  state: Arc<Mutex<PeerState>>,
+  /// 両者が共通で使うlevel生成用のseed
+  pub seed: u64,
+}
+
+/// 自分の状態を送信するメッセージにします
+/// * `state` - 送信する状態
+fn encode_state(state: &PeerState) -> String {
+  format!("state {} {} {}\n", state.level, state.score, state.alive as u8)
+}
+
+/// 受信したメッセージから相手の状態を読み込みます
+/// 形式が違う場合は`None`を返します
+/// * `line` - 受信したメッセージ
+fn decode_state(line: &str) -> Option<PeerState> {
+  let words: Vec<&str> = line.split_whitespace().collect();
+  if words.len() != 4 || words[0] != "state" {
+    return None;
+  }
+  Some(PeerState {
+    level: words[1].parse().ok()?,
+    score: words[2].parse().ok()?,
+    alive: words[3] == "1",
+    connected: true,
+  })
+}
+
+impl Peer {
+  /// 対戦相手の接続を待ち、共通のseedを送信します
+  /// * `listener` - 接続を待つリスナー
+  pub fn host(listener: &TcpListener) -> io::Result<Peer> {
+    let (mut stream, _) = listener.accept()?;
+    let seed = rand::random::<u64>();
+    stream.write_all(format!("seed {}\n", seed).as_bytes())?;
+    let reader = BufReader::new(stream.try_clone()?);
+    Ok(Peer::start(stream, reader, seed))
+  }
+
+  /// 対戦相手に接続し、共通のseedを受信します
+  /// ポート番号が省略された場合は`DEFAULT_PORT`に接続します
+  /// * `addr` - 接続先のアドレス
+  pub fn join(addr: &str) -> io::Result<Peer> {
+    let stream = if addr.contains(':') {
+      TcpStream::connect(addr)?
+    } else {
+      TcpStream::connect((addr, DEFAULT_PORT))?
+    };
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let seed = line.trim().strip_prefix("seed ").and_then(|s| s.parse().ok())
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid handshake"))?;
+    Ok(Peer::start(stream, reader, seed))
+  }
+
+  /// 相手の状態を受信するスレッドを開始します
+  /// * `stream` - 相手との接続
+  /// * `reader` - 相手からの受信に使うリーダー
+  /// * `seed` - 共通のseed
+  fn start(stream: TcpStream, reader: BufReader<TcpStream>, seed: u64) -> Peer {
+    let state = Arc::new(Mutex::new(PeerState::default()));
+    let shared = Arc::clone(&state);
+    thread::spawn(move || {
+      for line in reader.lines() {
+        match line.ok().as_deref().and_then(decode_state) {
+          Some(s) => { *shared.lock().unwrap() = s; },
+          None => break,
+        }
+      }
+      shared.lock().unwrap().connected = false;
+    });
+    Peer { stream, state, seed }
+  }
+
+  /// 自分の状態を相手に送信します
+  /// * `level` - 自分のlevel
+  /// * `score` - 自分のscore
+  /// * `alive` - 自分が生き残っているか
+  pub fn send(&mut self, level: u32, score: u64, alive: bool) -> io::Result<()> {
+    let state = PeerState { level, score, alive, connected: true };
+    self.stream.write_all(encode_state(&state).as_bytes())
+  }
+
+  /// 最後に受信した相手の状態を返します
+  pub fn state(&self) -> PeerState {
+    *self.state.lock().unwrap()
+  }
+
+  /// 指定のlevelのfieldを生成するための乱数生成器を返します
+  /// 両者で同じlevelには同じfieldが生成されます
+  /// * `level` - 生成するlevel
+  pub fn level_rng(&self, level: u32) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+    rng.set_stream(level as u64);
+    rng
+  }
+}
+
+impl Drop for Peer {
+  fn drop(&mut self) {
+    // 受信スレッドを終了させる
+    let _ = self.stream.shutdown(Shutdown::Both);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::{Duration, Instant};
+  use rand::Rng;
+
+  /// 相手の状態が条件を満たすまで待ちます
+  fn wait_for<F: Fn(&PeerState) -> bool>(peer: &Peer, cond: F) -> PeerState {
+    let start = Instant::now();
+    while !cond(&peer.state()) {
+      assert!(start.elapsed() < Duration::from_secs(5), "timed out: {:?}", peer.state());
+      thread::sleep(Duration::from_millis(10));
+    }
+    peer.state()
+  }
+
+#[test]
+  fn state_message_test() {
+    let state = PeerState { level: 3, score: 120, alive: false, connected: true };
+    assert_eq!(decode_state(&encode_state(&state)), Some(state));
+    assert_eq!(decode_state("state 1 2"), None);
+    assert_eq!(decode_state("hello 1 2 1"), None);
+  }
+
+#[test]
+  fn localhost_versus_test() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let client = thread::spawn(move || Peer::join(&addr).unwrap());
+    let mut host = Peer::host(&listener).unwrap();
+    let mut guest = client.join().unwrap();
+
+    assert_eq!(host.seed, guest.seed);
+    assert_eq!(host.level_rng(2).gen::<u64>(), guest.level_rng(2).gen::<u64>());
+    assert_ne!(host.level_rng(1).gen::<u64>(), guest.level_rng(2).gen::<u64>());
+
+    host.send(2, 30, true).unwrap();
+    let state = wait_for(&guest, |s| s.level == 2);
+    assert_eq!((state.score, state.alive), (30, true));
+
+    guest.send(1, 5, false).unwrap();
+    let state = wait_for(&host, |s| !s.alive);
+    assert_eq!((state.level, state.score), (1, 5));
+
+    drop(guest);
+    wait_for(&host, |s| !s.connected);
+  }
+}
@@ -0,0 +1,92 @@
+use crate::internal::*;
+
+/// ゲームに同梱するパズルの盤面
+const PUZZLES: &str = include_str!("puzzles.txt");
+
+/// パズルモードの1問を表します
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+  /// パズルの名前
+  pub name: String,
+  /// 目標のターン数
+  pub par: u32,
+  /// パズルの盤面
+  pub field: Field,
+}
+
+/// パズルの定義を読み込みます
+/// 形式が違う場合は`None`を返します
+/// * `s` - パズルの定義
+/// * `pos` - fieldが配置される場所
+pub fn parse_puzzles(s: &str, pos: Point) -> Option<Vec<Puzzle>> {
+  let mut puzzles = Vec::new();
+  let mut lines = s.lines().map(|l| l.trim_end()).filter(|l| !l.is_empty() && !l.starts_with('#'));
+  while let Some(line) = lines.next() {
+    let name = line.strip_prefix("puzzle ")?.trim().to_string();
+    let par = lines.next()?.strip_prefix("par ")?.trim().parse().ok()?;
+    let rows: Vec<&str> = lines.by_ref().take_while(|l| *l != "end").collect();
+    let field = Field::from_rows(&rows, pos)?;
+    puzzles.push(Puzzle { name, par, field });
+  }
+  Some(puzzles)
+}
+
+/// ゲームに同梱するパズルを返します
+/// * `pos` - fieldが配置される場所
+pub fn puzzles(pos: Point) -> Vec<Puzzle> {
+  parse_puzzles(PUZZLES, pos).expect("パズルのデータの形式が違います")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::VecDeque;
+
+  /// ランダム移動を使わずに全てのrobotを倒せる最短のターン数を返します
+  /// * `field` - 解くパズルの盤面
+  /// * `limit` - 探索するターン数の上限
+  fn solve(field: &Field, limit: u32) -> Option<u32> {
+    let moves = [(-1, -1), (0, -1), (1, -1), (-1, 0), (0, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+    let mut queue = VecDeque::new();
+    queue.push_back((field.clone(), 0));
+    while let Some((field, turns)) = queue.pop_front() {
+      if turns >= limit {
+        continue;
+      }
+      for (dx, dy) in moves.iter() {
+        let x = field.player_pos.x as i64 + dx;
+        let y = field.player_pos.y as i64 + dy;
+        if x < 0 || y < 0 || x >= field.width as i64 || y >= field.height as i64 {
+          continue;
+        }
+        let mut next = field.clone();
+        if !next.player_move(Point::new(x as usize, y as usize)) || next.robots_move(false).is_none() {
+          continue;
+        }
+        if next.all_robots_destroyed() {
+          return Some(turns + 1);
+        }
+        queue.push_back((next, turns + 1));
+      }
+    }
+    None
+  }
+
+#[test]
+  fn parse_puzzles_test() {
+    let puzzles = parse_puzzles("# comment\npuzzle A\npar 2\n+.@\nend\n\npuzzle B\npar 1\n@\nend\n", Point::new(0, 0)).unwrap();
+    assert_eq!(puzzles.len(), 2);
+    assert_eq!((puzzles[0].name.as_str(), puzzles[0].par), ("A", 2));
+    assert_eq!(puzzles[1].field.width, 1);
+    assert!(parse_puzzles("puzzle A\n+.@\nend\n", Point::new(0, 0)).is_none());
+  }
+
+#[test]
+  fn shipped_puzzles_test() {
+    let puzzles = puzzles(Point::new(5, 5));
+    assert!(!puzzles.is_empty());
+    for puzzle in puzzles {
+      assert_eq!(solve(&puzzle.field, puzzle.par), Some(puzzle.par), "{}", puzzle.name);
+    }
+  }
+}
@@ -0,0 +1,376 @@
+use std::collections::HashSet;
+use std::fs;
+use rand_chacha::ChaCha8Rng;
+use rand::SeedableRng;
+use crate::internal::*;
+use crate::score::{ScoreBreakdown, ScoreEngine};
+use crate::difficulty::{Difficulty, Rules};
+use crate::config::MAX_FIELD_SIZE;
+
+/// セーブファイルの先頭に書かれる識別子
+const SAVE_HEADER: &str = "robots-save";
+/// セーブファイルの形式のバージョン
+/// 2でscoreの計算の状態、3で難易度、4でsmart_robotを追加しました
+const SAVE_VERSION: u32 = 4;
+
+/// セーブファイルを読み込めなかった理由を表します
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SaveError {
+  /// ファイルを読み込めない
+  Io(std::io::ErrorKind),
+  /// セーブファイルではない
+  NotASave,
+  /// このバージョンでは読み込めない新しい形式
+  UnsupportedVersion(u32),
+  /// 指定のキーワードの行が無いか形式が違う
+  Malformed(&'static str),
+  /// fieldが大きすぎる
+  TooLarge {
+    /// fieldの横幅
+    width: usize,
+    /// fieldの縦幅
+    height: usize,
+  },
+  /// 座標がfieldの外にある
+  OutOfBounds(Point),
+  /// 同じマスに複数のobjectがある
+  Overlap(Point),
+}
+
+impl std::fmt::Display for SaveError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      SaveError::Io(kind) => write!(f, "cannot read the file ({})", kind),
+      SaveError::NotASave => write!(f, "not a save file"),
+      SaveError::UnsupportedVersion(version) =>
+        write!(f, "save format {} is newer than this version supports ({})", version, SAVE_VERSION),
+      SaveError::Malformed(key) => write!(f, "the {} line is missing or malformed", key),
+      SaveError::TooLarge { width, height } =>
+        write!(f, "the field size {}x{} exceeds {}x{}", width, height, MAX_FIELD_SIZE, MAX_FIELD_SIZE),
+      SaveError::OutOfBounds(p) => write!(f, "({}, {}) is outside the field", p.x, p.y),
+      SaveError::Overlap(p) => write!(f, "({}, {}) holds more than one object", p.x, p.y),
+    }
+  }
+}
+
+impl std::error::Error for SaveError {}
+
+impl SaveError {
+  /// セーブファイルが壊れているかを返します
+  /// 読み込みに失敗した場合や新しい形式の場合は、後で読み込める可能性があるので`false`を返します
+  pub fn is_corrupt(&self) -> bool {
+    !matches!(self, SaveError::Io(_) | SaveError::UnsupportedVersion(_))
+  }
+}
+
+/// 中断したゲームの状態を表します
+pub struct SaveData {
+  /// 現在のlevel
+  pub level: u32,
+  /// 現在のscore
+  pub score: u64,
+  /// scoreの計算の状態
+  pub scoring: ScoreEngine,
+  /// 難易度とランダム移動の残り回数
+  pub rules: Rules,
+  /// ゲームで使う乱数生成器
+  pub rng: ChaCha8Rng,
+  /// 現在のfield
+  pub field: Field,
+}
+
+/// 座標のリストを`x,y`を空白区切りで並べた文字列にします
+/// * `points` - 座標のリスト
+fn encode_points<'a, I: Iterator<Item = &'a Point>>(points: I) -> String {
+  points.map(|p| format!("{},{}", p.x, p.y)).collect::<Vec<String>>().join(" ")
+}
+
+/// `x,y`を空白区切りで並べた文字列を座標のリストにします
+/// 形式が違う場合は`None`を返します
+/// * `s` - 座標のリストを表す文字列
+fn decode_points(s: &str) -> Option<Vec<Point>> {
+  s.split_whitespace().map(|p| {
+    let mut xy = p.split(',');
+    let x = xy.next()?.parse().ok()?;
+    let y = xy.next()?.parse().ok()?;
+    match xy.next() {
+      None => Some(Point::new(x, y)),
+      _ => None,
+    }
+  }).collect()
+}
+
+/// 行の先頭のキーワードを確認し、残りの文字列を返します
+/// * `line` - 読み込む行
+/// * `key` - 行の先頭にあるべきキーワード
+fn strip_key<'a>(line: Option<&'a str>, key: &str) -> Option<&'a str> {
+  let line = line?;
+  if line == key {
+    Some("")
+  } else {
+    line.strip_prefix(key)?.strip_prefix(' ')
+  }
+}
+
+/// 次の行を読み込み、キーワードの後の文字列を解析します
+/// 行が無い場合や解析できない場合は`SaveError::Malformed`を返します
+/// * `lines` - 読み込む行のイテレータ
+/// * `key` - 行の先頭にあるべきキーワード
+/// * `parse` - キーワードの後の文字列を解析する関数
+fn read_line<'a, I, T, F>(lines: &mut I, key: &'static str, parse: F) -> Result<T, SaveError>
+  where I: Iterator<Item = &'a str>, F: FnOnce(&'a str) -> Option<T> {
+  strip_key(lines.next(), key).and_then(parse).ok_or(SaveError::Malformed(key))
+}
+
+/// fieldの状態を文字列にします
+/// fieldの画面上の位置は含みません
+/// * `field` - 変換するfield
+pub fn encode_field(field: &Field) -> String {
+  let mut scraps: Vec<&Point> = field.scraps_pos.iter().collect();
+  scraps.sort_by_key(|p| (p.y, p.x));
+  format!("size {} {}\nplayer {}\nrobots {}\nsuper_robots {}\nsmart_robots {}\nscraps {}\n",
+    field.width, field.height,
+    encode_points([field.player_pos].iter()),
+    encode_points(field.robots_pos.iter()),
+    encode_points(field.super_robots_pos.iter()),
+    encode_points(field.smart_robots_pos.iter()),
+    encode_points(scraps.into_iter()))
+}
+
+/// `encode_field`で作った文字列からfieldを復元します
+/// 形式が違う場合や、座標がfieldの外にあるか重なっている場合はエラーを返します
+/// * `lines` - fieldを表す行のイテレータ
+/// * `pos` - fieldが配置される場所
+pub fn decode_field<'a, I: Iterator<Item = &'a str>>(lines: &mut I, pos: Point) -> Result<Field, SaveError> {
+  decode_versioned_field(lines, pos, SAVE_VERSION)
+}
+
+/// 指定のバージョンの形式で書かれたfieldを復元します
+/// * `lines` - fieldを表す行のイテレータ
+/// * `pos` - fieldが配置される場所
+/// * `version` - セーブファイルの形式のバージョン
+fn decode_versioned_field<'a, I: Iterator<Item = &'a str>>(lines: &mut I, pos: Point, version: u32) -> Result<Field, SaveError> {
+  let size = read_line(lines, "size", decode_numbers)?;
+  if size.len() != 2 || size[0] == 0 || size[1] == 0 {
+    return Err(SaveError::Malformed("size"));
+  }
+  let (width, height) = (size[0], size[1]);
+  // gridを確保する前に大きさを確かめる
+  if width > MAX_FIELD_SIZE || height > MAX_FIELD_SIZE {
+    return Err(SaveError::TooLarge { width, height });
+  }
+  let player = read_line(lines, "player", decode_points)?;
+  let robots = read_line(lines, "robots", decode_points)?;
+  let super_robots = read_line(lines, "super_robots", decode_points)?;
+  let smart_robots = match version {
+    v if v >= 4 => read_line(lines, "smart_robots", decode_points)?,
+    _ => Vec::new(),
+  };
+  let scraps = read_line(lines, "scraps", decode_points)?;
+  if player.len() != 1 {
+    return Err(SaveError::Malformed("player"));
+  }
+
+  let mut occupied = HashSet::new();
+  for p in player.iter().chain(robots.iter()).chain(super_robots.iter()).chain(smart_robots.iter()).chain(scraps.iter()) {
+    if p.x >= width || p.y >= height {
+      return Err(SaveError::OutOfBounds(*p));
+    }
+    if !occupied.insert(*p) {
+      return Err(SaveError::Overlap(*p));
+    }
+  }
+  let mut field = Field::from_parts(pos, width, height, player[0], robots, super_robots,
+    scraps.into_iter().collect::<HashSet<Point>>());
+  field.smart_robots_pos = smart_robots;
+  field.field_update();
+  Ok(field)
+}
+
+/// 空白区切りの数値のリストを読み込みます
+/// * `s` - 数値のリストを表す文字列
+fn decode_numbers(s: &str) -> Option<Vec<usize>> {
+  s.split_whitespace().map(|n| n.parse().ok()).collect()
+}
+
+/// ゲームの状態を文字列にします
+/// * `level` - 現在のlevel
+/// * `score` - 現在のscore
+/// * `scoring` - scoreの計算の状態
+/// * `rules` - 難易度とランダム移動の残り回数
+/// * `rng` - ゲームで使う乱数生成器
+/// * `field` - 現在のfield
+pub fn encode_game(level: u32, score: u64, scoring: &ScoreEngine, rules: &Rules, rng: &ChaCha8Rng, field: &Field) -> String {
+  let seed: String = rng.get_seed().iter().map(|b| format!("{:02x}", b)).collect();
+  let teleports = rules.teleports_left.map_or("-".to_string(), |n| n.to_string());
+  format!("{} {}\nlevel {}\nscore {}\nscoring {} {} {}\ndifficulty {} {}\nrng {} {} {}\n{}",
+    SAVE_HEADER, SAVE_VERSION, level, score, scoring.chain, scoring.teleported as u8, scoring.totals.encode(),
+    rules.difficulty.name(), teleports, seed, rng.get_stream(), rng.get_word_pos(), encode_field(field))
+}
+
+/// `encode_game`で作った文字列からゲームの状態を復元します
+/// 古いバージョンの形式の場合、無い項目は新しいゲームと同じ状態にします
+/// 形式が違う場合はエラーを返します
+/// * `s` - ゲームの状態を表す文字列
+/// * `pos` - fieldが配置される場所
+pub fn decode_game(s: &str, pos: Point) -> Result<SaveData, SaveError> {
+  let mut lines = s.lines();
+  let version: u32 = strip_key(lines.next(), SAVE_HEADER).and_then(|v| v.parse().ok()).ok_or(SaveError::NotASave)?;
+  if version > SAVE_VERSION {
+    return Err(SaveError::UnsupportedVersion(version));
+  }
+  let level = read_line(&mut lines, "level", |l| l.parse().ok())?;
+  let score = read_line(&mut lines, "score", |l| l.parse().ok())?;
+  let mut scoring = match version {
+    v if v >= 2 => read_line(&mut lines, "scoring", |l| {
+      let (chain, rest) = l.split_once(' ')?;
+      let (teleported, totals) = rest.split_once(' ')?;
+      Some(ScoreEngine {
+        percent: 100,
+        chain: chain.parse().ok()?,
+        teleported: teleported == "1",
+        last: ScoreBreakdown::default(),
+        totals: ScoreBreakdown::decode(totals)?,
+      })
+    })?,
+    _ => ScoreEngine::default(),
+  };
+  let rules = match version {
+    v if v >= 3 => read_line(&mut lines, "difficulty", |l| {
+      let (difficulty, teleports) = l.split_once(' ')?;
+      Some(Rules {
+        difficulty: Difficulty::parse(difficulty)?,
+        teleports_left: match teleports {
+          "-" => None,
+          n => Some(n.parse().ok()?),
+        },
+      })
+    })?,
+    _ => Rules::new(Difficulty::Normal),
+  };
+  scoring.percent = rules.difficulty.preset().score_percent;
+
+  let rng = read_line(&mut lines, "rng", |l| {
+    let rng_str: Vec<&str> = l.split_whitespace().collect();
+    if rng_str.len() != 3 || rng_str[0].len() != 64 {
+      return None;
+    }
+    let mut seed = [0u8; 32];
+    for (i, byte) in seed.iter_mut().enumerate() {
+      *byte = u8::from_str_radix(rng_str[0].get(i*2..i*2+2)?, 16).ok()?;
+    }
+    let mut rng = ChaCha8Rng::from_seed(seed);
+    rng.set_stream(rng_str[1].parse().ok()?);
+    rng.set_word_pos(rng_str[2].parse().ok()?);
+    Some(rng)
+  })?;
+
+  let field = decode_versioned_field(&mut lines, pos, version)?;
+  Ok(SaveData { level, score, scoring, rules, rng, field })
+}
+
+/// ゲームの状態をセーブファイルに書き込みます
+/// * `filename` - セーブファイル名
+/// * `level` - 現在のlevel
+/// * `score` - 現在のscore
+/// * `scoring` - scoreの計算の状態
+/// * `rules` - 難易度とランダム移動の残り回数
+/// * `rng` - ゲームで使う乱数生成器
+/// * `field` - 現在のfield
+pub fn save_game(filename: &str, level: u32, score: u64, scoring: &ScoreEngine, rules: &Rules, rng: &ChaCha8Rng,
+  field: &Field) -> std::io::Result<()> {
+  fs::write(filename, encode_game(level, score, scoring, rules, rng, field))
+}
+
+/// セーブファイルからゲームの状態を読み込みます
+/// ファイルを読み込めない場合や形式が違う場合はエラーを返します
+/// * `filename` - セーブファイル名
+/// * `pos` - fieldが配置される場所
+pub fn load_game(filename: &str, pos: Point) -> Result<SaveData, SaveError> {
+  decode_game(&fs::read_to_string(filename).map_err(|e| SaveError::Io(e.kind()))?, pos)
+}
+
+/// セーブファイルを削除します
+/// * `filename` - セーブファイル名
+pub fn remove_save(filename: &str) {
+  let _ = fs::remove_file(filename);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::Rng;
+
+#[test]
+  fn encode_decode_game_test() {
+    let mut rng = ChaCha8Rng::seed_from_u64(42);
+    let mut field = Field::with_rng(Point::new(5, 5), 30, 10, 12, &mut rng);
+    field.scraps_pos.insert(Point::new(0, 0));
+    field.scraps_pos.insert(Point::new(29, 9));
+    rng.gen::<u64>();
+    let mut scoring = ScoreEngine::default();
+    scoring.score_turn(2, &TurnReport { robots: 2, ..TurnReport::default() });
+    scoring.teleport();
+
+    let mut rules = Rules::new(Difficulty::Hard);
+    rules.teleport();
+
+    let data = decode_game(&encode_game(3, 120, &scoring, &rules, &rng, &field), Point::new(5, 5)).unwrap();
+    assert_eq!(data.level, 3);
+    assert_eq!(data.score, 120);
+    assert_eq!((data.scoring.chain, data.scoring.teleported), (1, true));
+    assert_eq!(data.scoring.totals, scoring.totals);
+    assert_eq!(data.scoring.percent, 150);
+    assert_eq!(data.rules, rules);
+    assert_eq!(data.field.player_pos, field.player_pos);
+    assert_eq!(data.field.robots_pos, field.robots_pos);
+    assert_eq!(data.field.super_robots_pos, field.super_robots_pos);
+    assert_eq!(data.field.scraps_pos, field.scraps_pos);
+    assert!(matches!(data.field.field[9][29], Object::Scrap));
+
+    let mut restored = data.rng;
+    assert_eq!(restored.gen::<u64>(), rng.gen::<u64>());
+  }
+
+#[test]
+  fn decode_game_error_test() {
+    let rng = ChaCha8Rng::seed_from_u64(0);
+    let field = Field::new(Point::new(0, 0), 10, 10, 0);
+    let save = encode_game(1, 0, &ScoreEngine::default(), &Rules::new(Difficulty::Normal), &rng, &field);
+    let decode = |s: &str| decode_game(s, Point::new(0, 0)).err();
+
+    assert_eq!(decode(""), Some(SaveError::NotASave));
+    assert_eq!(decode(&save.replace("robots-save", "robots")), Some(SaveError::NotASave));
+    assert_eq!(decode(&save.replace("robots-save 4", "robots-save 9")), Some(SaveError::UnsupportedVersion(9)));
+    assert_eq!(decode(&save.replace("level 1", "level x")), Some(SaveError::Malformed("level")));
+    assert_eq!(decode(&save.replace("scoring 0 0", "scoring 0")), Some(SaveError::Malformed("scoring")));
+    assert_eq!(decode(&save.replace("difficulty normal", "difficulty insane")), Some(SaveError::Malformed("difficulty")));
+    assert_eq!(decode(&save.replace("\nrobots \n", "\nrobots 10,0\n")), Some(SaveError::OutOfBounds(Point::new(10, 0))));
+    assert_eq!(decode(&save.replace("\nrobots \n", "\nrobots 1,2,3\n")), Some(SaveError::Malformed("robots")));
+    assert_eq!(decode(&save.replace("size 10 10", "size 100000 100000")),
+      Some(SaveError::TooLarge { width: 100000, height: 100000 }));
+    assert_eq!(decode(&save.replace("\nrobots \n", "\nrobots 5,5\n")), Some(SaveError::Overlap(Point::new(5, 5))));
+    assert_eq!(decode(&save.replace("\nrobots \n", "\nrobots 1,1 1,1\n")), Some(SaveError::Overlap(Point::new(1, 1))));
+    assert_eq!(decode(&save.replace("\nscraps \n", "\nscraps 3,3\n").replace("\nrobots \n", "\nrobots 3,3\n")),
+      Some(SaveError::Overlap(Point::new(3, 3))));
+    // 後で読み込める可能性がある場合は壊れているとみなさない
+    assert!(SaveError::Malformed("level").is_corrupt());
+    assert!(!SaveError::UnsupportedVersion(9).is_corrupt());
+    assert!(!SaveError::Io(std::io::ErrorKind::PermissionDenied).is_corrupt());
+  }
+
+#[test]
+  fn decode_old_version_test() {
+    // バージョン1にはscoreの計算の状態、難易度、smart_robotの行が無い
+    let rng = ChaCha8Rng::seed_from_u64(0);
+    let seed: String = rng.get_seed().iter().map(|b| format!("{:02x}", b)).collect();
+    let save = format!("robots-save 1\nlevel 2\nscore 30\nrng {} 0 0\nsize 10 5\nplayer 5,2\nrobots 0,0 9,4\nsuper_robots 1,1\nscraps 3,3\n", seed);
+    let data = decode_game(&save, Point::new(0, 0)).unwrap();
+    assert_eq!((data.level, data.score), (2, 30));
+    assert_eq!(data.rules, Rules::new(Difficulty::Normal));
+    assert_eq!(data.scoring.chain, 0);
+    assert_eq!(data.field.robots_pos, vec![Point::new(0, 0), Point::new(9, 4)]);
+    assert!(data.field.smart_robots_pos.is_empty());
+    assert!(matches!(data.field.field[3][3], Object::Scrap));
+  }
+}
@@ -0,0 +1,209 @@
+use crate::internal::TurnReport;
+
+/// 連続で得点したターンのボーナスの上限
+const MAX_CHAIN_BONUS: u64 = 10;
+
+/// 獲得したscoreの内訳を表します
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScoreBreakdown {
+  /// 倒したrobotの基本点(robotは1点、super_robotは2点)
+  pub base: u64,
+  /// 1ターンで複数のrobotを倒したボーナス
+  pub combo: u64,
+  /// 連続したターンで得点したボーナス
+  pub chain: u64,
+  /// levelの中でランダム移動を使わずに倒したボーナス
+  pub clean: u64,
+  /// 止まるモードで倒したボーナス
+  pub stop: u64,
+  /// levelをクリアしたボーナス
+  pub clear: u64,
+}
+
+impl ScoreBreakdown {
+  /// 合計のscoreを返します
+  pub fn total(&self) -> u64 {
+    self.base + self.combo + self.chain + self.clean + self.stop + self.clear
+  }
+
+  /// 内訳を足し合わせます
+  /// * `other` - 足す内訳
+  pub fn add(&mut self, other: &ScoreBreakdown) {
+    self.base += other.base;
+    self.combo += other.combo;
+    self.chain += other.chain;
+    self.clean += other.clean;
+    self.stop += other.stop;
+    self.clear += other.clear;
+  }
+
+  /// 内訳を空白区切りの数値にします
+  pub fn encode(&self) -> String {
+    format!("{} {} {} {} {} {}", self.base, self.combo, self.chain, self.clean, self.stop, self.clear)
+  }
+
+  /// 各項目に倍率を掛けた内訳を返します
+  /// * `percent` - 倍率(パーセント)
+  pub fn scale(&self, percent: u64) -> ScoreBreakdown {
+    let f = |n: u64| n * percent / 100;
+    ScoreBreakdown {
+      base: f(self.base),
+      combo: f(self.combo),
+      chain: f(self.chain),
+      clean: f(self.clean),
+      stop: f(self.stop),
+      clear: f(self.clear),
+    }
+  }
+
+  /// `encode`で作った文字列から内訳を読み込みます
+  /// 形式が違う場合は`None`を返します
+  /// * `s` - 内訳を表す文字列
+  pub fn decode(s: &str) -> Option<ScoreBreakdown> {
+    let n: Vec<u64> = s.split_whitespace().map(|n| n.parse().ok()).collect::<Option<Vec<u64>>>()?;
+    if n.len() != 6 {
+      return None;
+    }
+    Some(ScoreBreakdown { base: n[0], combo: n[1], chain: n[2], clean: n[3], stop: n[4], clear: n[5] })
+  }
+}
+
+/// ターンごとのscoreとゲーム全体の内訳を計算します
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScoreEngine {
+  /// 獲得するscoreの倍率(パーセント)
+  pub percent: u64,
+  /// 連続で得点したターン数
+  pub chain: u64,
+  /// 現在のlevelでランダム移動を使ったか
+  pub teleported: bool,
+  /// 直前のターンの内訳
+  pub last: ScoreBreakdown,
+  /// ゲーム全体の内訳
+  pub totals: ScoreBreakdown,
+}
+
+impl Default for ScoreEngine {
+  fn default() -> ScoreEngine {
+    ScoreEngine::new(100)
+  }
+}
+
+impl ScoreEngine {
+  /// 指定の倍率でscoreを計算する状態を生成します
+  /// * `percent` - 獲得するscoreの倍率(パーセント)
+  pub fn new(percent: u64) -> ScoreEngine {
+    ScoreEngine {
+      percent,
+      chain: 0,
+      teleported: false,
+      last: ScoreBreakdown::default(),
+      totals: ScoreBreakdown::default(),
+    }
+  }
+
+  /// levelの開始時に連続得点とランダム移動の記録をリセットします
+  pub fn start_level(&mut self) {
+    self.chain = 0;
+    self.teleported = false;
+  }
+
+  /// ランダム移動を使ったことを記録します
+  pub fn teleport(&mut self) {
+    self.teleported = true;
+  }
+
+  /// robotの移動で獲得したscoreを計算します
+  /// * `turn_score` - `Field::robots_move`が返したscore
+  /// * `report` - robotの移動で倒したrobotの数
+  pub fn score_turn(&mut self, turn_score: u64, report: &TurnReport) -> ScoreBreakdown {
+    let kills = (report.robots + report.super_robots) as u64;
+    let base = report.robots as u64 + report.super_robots as u64 * 2;
+    let mut turn = ScoreBreakdown {
+      base,
+      stop: turn_score.saturating_sub(base),
+      ..ScoreBreakdown::default()
+    };
+    if kills == 0 {
+      self.chain = 0;
+    } else {
+      self.chain += 1;
+      // 2体で1点、3体で3点、4体で6点...
+      turn.combo = kills * (kills - 1) / 2;
+      turn.chain = std::cmp::min(self.chain - 1, MAX_CHAIN_BONUS);
+      if !self.teleported {
+        turn.clean = kills;
+      }
+    }
+    let turn = turn.scale(self.percent);
+    self.last = turn;
+    self.totals.add(&turn);
+    turn
+  }
+
+  /// levelをクリアしたボーナスを記録します
+  /// * `level` - クリアしたlevel
+  pub fn clear_level(&mut self, level: u32) -> u64 {
+    let bonus = (level * 10) as u64 * self.percent / 100;
+    self.totals.clear += bonus;
+    bonus
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn score_turn_test() {
+    let mut engine = ScoreEngine::default();
+    engine.start_level();
+
+    let turn = engine.score_turn(0, &TurnReport::default());
+    assert_eq!(turn.total(), 0);
+    assert_eq!(engine.chain, 0);
+
+    // 2体のrobotと1体のsuper_robotを倒した
+    let report = TurnReport { robots: 2, super_robots: 1, killed_by: None };
+    let turn = engine.score_turn(4, &report);
+    assert_eq!(turn, ScoreBreakdown { base: 4, combo: 3, chain: 0, clean: 3, stop: 0, clear: 0 });
+
+    // 連続で得点すると連鎖ボーナス、ランダム移動後はクリーンボーナスなし
+    engine.teleport();
+    let report = TurnReport { robots: 2, ..TurnReport::default() };
+    let turn = engine.score_turn(4, &report);
+    assert_eq!(turn, ScoreBreakdown { base: 2, combo: 1, chain: 1, clean: 0, stop: 2, clear: 0 });
+
+    engine.score_turn(0, &TurnReport::default());
+    assert_eq!(engine.chain, 0);
+    assert_eq!(engine.clear_level(2), 20);
+    assert_eq!(engine.totals.total(), 10 + 6 + 20);
+  }
+
+#[test]
+  fn chain_bonus_limit_test() {
+    let mut engine = ScoreEngine::default();
+    let report = TurnReport { robots: 1, ..TurnReport::default() };
+    for _ in 0..20 {
+      engine.score_turn(1, &report);
+    }
+    assert_eq!(engine.last.chain, MAX_CHAIN_BONUS);
+  }
+
+#[test]
+  fn percent_test() {
+    let mut engine = ScoreEngine::new(150);
+    let report = TurnReport { robots: 3, ..TurnReport::default() };
+    let turn = engine.score_turn(3, &report);
+    assert_eq!(turn, ScoreBreakdown { base: 4, combo: 4, chain: 0, clean: 4, stop: 0, clear: 0 });
+    assert_eq!(engine.clear_level(1), 15);
+    assert_eq!(engine.totals.total(), 12 + 15);
+  }
+
+#[test]
+  fn breakdown_encode_test() {
+    let b = ScoreBreakdown { base: 1, combo: 2, chain: 3, clean: 4, stop: 5, clear: 6 };
+    assert_eq!(ScoreBreakdown::decode(&b.encode()), Some(b));
+    assert_eq!(ScoreBreakdown::decode("1 2 3"), None);
+  }
+}
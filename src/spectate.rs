@@ -0,0 +1,324 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::internal::*;
+use crate::save::{encode_field, decode_field};
+
+/// 観戦で使うデフォルトのポート番号
+pub const DEFAULT_PORT: u16 = 7879;
+
+/// 観戦者ごとに送信を待てるフレームの数
+/// これより遅れた観戦者は切断します
+const WATCHER_QUEUE: usize = 16;
+/// 観戦者への1回の書き込みを待つ時間
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// 接続が無いときに次の接続を確かめるまでの間隔
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 観戦者ごとの送信スレッドにフレームを渡すチャンネルのリスト
+type Watchers = Arc<Mutex<Vec<SyncSender<Arc<String>>>>>;
+
+/// 観戦者を登録し、フレームを送信するスレッドを開始します
+/// ゲームのスレッドは観戦者への書き込みを待たずに済みます
+/// * `watchers` - 観戦者のリスト
+/// * `stream` - 観戦者への接続
+fn add_watcher<W: Write + Send + 'static>(watchers: &Watchers, mut stream: W) {
+  let (tx, rx) = mpsc::sync_channel::<Arc<String>>(WATCHER_QUEUE);
+  thread::spawn(move || {
+    for frame in rx {
+      if stream.write_all(frame.as_bytes()).is_err() {
+        break;
+      }
+    }
+  });
+  watchers.lock().unwrap().push(tx);
+}
+
+/// `stop`が立つまで観戦者の接続を受け付けるスレッドを開始します
+/// 終了を確かめられるように、待ち受けはノンブロッキングにしておきます
+/// * `watchers` - 観戦者のリスト
+/// * `stop` - 受け付けを終了するかのフラグ
+/// * `accept` - 接続を1つ受け付ける関数(接続が無い場合は`WouldBlock`のエラーを返す)
+fn spawn_acceptor<W, F>(watchers: Watchers, stop: Arc<AtomicBool>, mut accept: F) -> JoinHandle<()>
+where
+  W: Write + Send + 'static,
+  F: FnMut() -> io::Result<W> + Send + 'static,
+{
+  thread::spawn(move || {
+    while !stop.load(Ordering::Relaxed) {
+      match accept() {
+        Ok(stream) => add_watcher(&watchers, stream),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+        // 受け付けに失敗した接続は無視する
+        Err(_) => (),
+      }
+    }
+  })
+}
+
+/// 観戦者に送る1フレーム分のゲームの状態を表します
+pub struct Frame {
+  /// 現在のlevel
+  pub level: u32,
+  /// 現在のscore
+  pub score: u64,
+  /// 現在のfield
+  pub field: Field,
+}
+
+/// アドレスがUnixソケットのパスを表す場合はそのパスを返します
+/// `unix:`で始まるか`/`を含むアドレスをUnixソケットとして扱います
+/// * `addr` - アドレス
+fn unix_path(addr: &str) -> Option<&str> {
+  match addr.strip_prefix("unix:") {
+    Some(path) => Some(path),
+    None if addr.contains('/') => Some(addr),
+    None => None,
+  }
+}
+
+/// ゲームの状態を観戦者に送るメッセージにします
+/// * `level` - 現在のlevel
+/// * `score` - 現在のscore
+/// * `field` - 現在のfield
+pub fn encode_frame(level: u32, score: u64, field: &Field) -> String {
+  let partner = match field.partner_pos {
+    Some(p) => format!("{},{}", p.x, p.y),
+    None => String::new(),
+  };
+  format!("frame {} {}\n{}partner {}\nalive {} {}\nend\n", level, score, encode_field(field),
+    partner, field.player_alive as u8, field.partner_alive as u8)
+}
+
+/// 受信したメッセージから1フレーム分のゲームの状態を読み込みます
+/// 接続が切れた場合や形式が違う場合は`None`を返します
+/// * `reader` - 観戦するゲームからの受信に使うリーダー
+/// * `pos` - fieldが配置される場所
+pub fn read_frame<R: BufRead>(reader: &mut R, pos: Point) -> Option<Frame> {
+  let mut lines = Vec::new();
+  loop {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+      return None;
+    }
+    let line = line.trim_end().to_string();
+    if line == "end" {
+      break;
+    }
+    lines.push(line);
+  }
+
+  let mut lines = lines.iter().map(|l| l.as_str());
+  let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+  if header.len() != 3 || header[0] != "frame" {
+    return None;
+  }
+  let mut field = decode_field(&mut lines, pos).ok()?;
+  let partner = lines.next()?.strip_prefix("partner")?.trim();
+  if !partner.is_empty() {
+    let (x, y) = partner.split_once(',')?;
+    let p = Point::new(x.parse().ok()?, y.parse().ok()?);
+    if p.x >= field.width || p.y >= field.height || field.field[p.y][p.x] != Object::Null {
+      return None;
+    }
+    field.partner_pos = Some(p);
+  }
+  let alive: Vec<&str> = lines.next()?.strip_prefix("alive")?.split_whitespace().collect();
+  if alive.len() != 2 {
+    return None;
+  }
+  field.player_alive = alive[0] == "1";
+  field.partner_alive = alive[1] == "1" && field.partner_pos.is_some();
+  field.field_update();
+
+  Some(Frame { level: header[1].parse().ok()?, score: header[2].parse().ok()?, field })
+}
+
+/// ゲームの状態を観戦者に配信します
+pub struct Broadcaster {
+  /// 接続している観戦者
+  watchers: Watchers,
+  /// 接続の受け付けを終了するかのフラグ
+  stop: Arc<AtomicBool>,
+  /// 接続を受け付けるスレッド
+  acceptor: Option<JoinHandle<()>>,
+  /// 待ち受けているUnixソケットのパス
+  socket_path: Option<String>,
+  /// 観戦者が接続するアドレス
+  pub addr: String,
+}
+
+impl Broadcaster {
+  /// 観戦者の接続の待ち受けを開始します
+  /// ポート番号だけが指定された場合はこのコンピュータからの接続(`127.0.0.1`)だけを待ち受けます
+  /// 他のコンピュータから観戦させる場合は`0.0.0.0:7879`のようにアドレスを指定します
+  /// * `addr` - 待ち受けるアドレス(ポート番号、`host:port`またはUnixソケットのパス)
+  pub fn bind(addr: &str) -> io::Result<Broadcaster> {
+    let watchers: Watchers = Arc::new(Mutex::new(Vec::new()));
+    let stop = Arc::new(AtomicBool::new(false));
+    let shared = Arc::clone(&watchers);
+    let (socket_path, local_addr, acceptor) = match unix_path(addr) {
+      Some(path) => {
+        // 前回のソケットファイルが残っていれば削除する
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        let acceptor = spawn_acceptor(shared, Arc::clone(&stop), move || {
+          let (stream, _) = listener.accept()?;
+          stream.set_nonblocking(false)?;
+          stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+          Ok(stream)
+        });
+        (Some(path.to_string()), format!("unix:{}", path), acceptor)
+      },
+      None => {
+        let listener = match addr.parse::<u16>() {
+          Ok(port) => TcpListener::bind(("127.0.0.1", port))?,
+          Err(_) => TcpListener::bind(addr)?,
+        };
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?.to_string();
+        let acceptor = spawn_acceptor(shared, Arc::clone(&stop), move || {
+          let (stream, _) = listener.accept()?;
+          stream.set_nonblocking(false)?;
+          stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+          Ok(stream)
+        });
+        (None, local_addr, acceptor)
+      },
+    };
+    Ok(Broadcaster { watchers, stop, acceptor: Some(acceptor), socket_path, addr: local_addr })
+  }
+
+  /// ゲームの状態を全ての観戦者の送信スレッドに渡します
+  /// 接続が切れた観戦者と、送信が`WATCHER_QUEUE`フレーム以上遅れている観戦者は切断します
+  /// * `level` - 現在のlevel
+  /// * `score` - 現在のscore
+  /// * `field` - 現在のfield
+  pub fn publish(&mut self, level: u32, score: u64, field: &Field) {
+    let frame = Arc::new(encode_frame(level, score, field));
+    self.watchers.lock().unwrap().retain(|tx| tx.try_send(Arc::clone(&frame)).is_ok());
+  }
+}
+
+impl Drop for Broadcaster {
+  fn drop(&mut self) {
+    // 新しい観戦者を受け付けないように、先に受け付けのスレッドを終了させる
+    self.stop.store(true, Ordering::Relaxed);
+    if let Some(acceptor) = self.acceptor.take() {
+      let _ = acceptor.join();
+    }
+    // 送信スレッドは残りのフレームを送った後に接続を切り、ゲームの終了を知らせる
+    self.watchers.lock().unwrap().clear();
+    if let Some(path) = &self.socket_path {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+}
+
+/// 観戦するゲームに接続します
+/// ポート番号が省略された場合は`DEFAULT_PORT`に接続します
+/// * `addr` - 接続先のアドレス(`host:port`、ホスト名またはUnixソケットのパス)
+pub fn connect(addr: &str) -> io::Result<BufReader<Box<dyn Read + Send>>> {
+  let stream: Box<dyn Read + Send> = match unix_path(addr) {
+    Some(path) => Box::new(UnixStream::connect(path)?),
+    None if addr.contains(':') => Box::new(TcpStream::connect(addr)?),
+    None => Box::new(TcpStream::connect((addr, DEFAULT_PORT))?),
+  };
+  Ok(BufReader::new(stream))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+#[test]
+  fn frame_test() {
+    let mut rng = rand::thread_rng();
+    let mut field = Field::with_partner(Point::new(5, 5), 30, 10, 8, &mut rng);
+    field.scraps_pos.insert(Point::new(0, 0));
+    field.partner_alive = false;
+
+    let msg = encode_frame(2, 40, &field);
+    let frame = read_frame(&mut msg.as_bytes(), Point::new(2, 3)).unwrap();
+    assert_eq!((frame.level, frame.score), (2, 40));
+    assert_eq!(frame.field.robots_pos, field.robots_pos);
+    assert_eq!(frame.field.partner_pos, field.partner_pos);
+    assert!(frame.field.player_alive);
+    assert!(!frame.field.partner_alive);
+    assert!(matches!(frame.field.field[0][0], Object::Scrap));
+    assert_eq!(frame.field.pos, Point::new(2, 3));
+
+    assert!(read_frame(&mut "frame 1 0\nend\n".as_bytes(), Point::new(5, 5)).is_none());
+    assert!(read_frame(&mut &msg.as_bytes()[..msg.len()-4], Point::new(5, 5)).is_none());
+  }
+
+  /// 観戦者が接続するのを待ってから配信し、最初のフレームを受信します
+  fn watch_first_frame(broadcaster: &mut Broadcaster, field: &Field) -> Frame {
+    let mut reader = connect(&broadcaster.addr).unwrap();
+    while broadcaster.watchers.lock().unwrap().is_empty() {
+      thread::sleep(Duration::from_millis(10));
+    }
+    broadcaster.publish(3, 15, field);
+    read_frame(&mut reader, Point::new(5, 5)).unwrap()
+  }
+
+#[test]
+  fn tcp_watch_test() {
+    let field = Field::new(Point::new(5, 5), 20, 10, 5);
+    let mut broadcaster = Broadcaster::bind("127.0.0.1:0").unwrap();
+
+    let frame = watch_first_frame(&mut broadcaster, &field);
+    assert_eq!((frame.level, frame.score), (3, 15));
+    assert_eq!(frame.field.robots_pos, field.robots_pos);
+  }
+
+#[test]
+  fn slow_watcher_test() {
+    // 受信しない観戦者が居てもゲームは止まらず、遅れた観戦者は切断される
+    let field = Field::new(Point::new(5, 5), 200, 100, 5000);
+    let mut broadcaster = Broadcaster::bind("127.0.0.1:0").unwrap();
+    let _reader = connect(&broadcaster.addr).unwrap();
+    while broadcaster.watchers.lock().unwrap().is_empty() {
+      thread::sleep(Duration::from_millis(10));
+    }
+    let mut published = 0;
+    while !broadcaster.watchers.lock().unwrap().is_empty() && published < 5000 {
+      let start = std::time::Instant::now();
+      broadcaster.publish(1, 0, &field);
+      assert!(start.elapsed() < Duration::from_secs(1));
+      published += 1;
+    }
+    assert!(broadcaster.watchers.lock().unwrap().is_empty(), "{} frames", published);
+  }
+
+#[test]
+  fn drop_stops_accepting_test() {
+    let broadcaster = Broadcaster::bind("127.0.0.1:0").unwrap();
+    let addr = broadcaster.addr.clone();
+    let _reader = connect(&addr).unwrap();
+    let start = std::time::Instant::now();
+    drop(broadcaster);
+    // 受け付けのスレッドが終了して待ち受けも閉じている
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(TcpStream::connect(&addr).is_err());
+  }
+
+#[test]
+  fn unix_watch_test() {
+    let path = std::env::temp_dir().join(format!("robots-watch-{}.sock", std::process::id()));
+    let field = Field::new(Point::new(5, 5), 20, 10, 5);
+    let mut broadcaster = Broadcaster::bind(&format!("unix:{}", path.display())).unwrap();
+
+    let frame = watch_first_frame(&mut broadcaster, &field);
+    assert_eq!(frame.field.super_robots_pos, field.super_robots_pos);
+    drop(broadcaster);
+    assert!(!path.exists());
+  }
+}
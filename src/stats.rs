@@ -0,0 +1,191 @@
+use std::fs;
+use crate::internal::*;
+
+/// 累計の記録の項目を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stat {
+  /// プレイしたゲームの数
+  GamesPlayed,
+  /// 倒したrobotの数
+  RobotsDestroyed,
+  /// 倒したsuper_robotの数
+  SuperRobotsDestroyed,
+  /// ランダム移動を使った回数
+  Teleports,
+  /// robotに倒された回数
+  DeathsByRobot,
+  /// scrapに倒された回数
+  DeathsByScrap,
+  /// 到達した最高のlevel
+  HighestLevel,
+  /// 止まるモードで生き残った最長のターン数
+  LongestStopStreak,
+}
+
+/// プレイヤーの累計の記録を表します
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+  /// プレイしたゲームの数
+  pub games_played: u64,
+  /// 倒したrobotの数
+  pub robots_destroyed: u64,
+  /// 倒したsuper_robotの数
+  pub super_robots_destroyed: u64,
+  /// ランダム移動を使った回数
+  pub teleports: u64,
+  /// robotに倒された回数
+  pub deaths_by_robot: u64,
+  /// scrapに倒された回数
+  pub deaths_by_scrap: u64,
+  /// 到達した最高のlevel
+  pub highest_level: u32,
+  /// 止まるモードで生き残った最長のターン数
+  pub longest_stop_streak: u64,
+}
+
+impl Stats {
+  /// robotの移動で起きたことを記録します
+  /// * `report` - robotの移動で起きたこと
+  pub fn record_turn(&mut self, report: &TurnReport) {
+    self.robots_destroyed += report.robots as u64;
+    self.super_robots_destroyed += report.super_robots as u64;
+    match report.killed_by {
+      Some(Object::Scrap) => { self.deaths_by_scrap += 1; },
+      Some(_) => { self.deaths_by_robot += 1; },
+      None => (),
+    }
+  }
+
+  /// 別の記録を足し合わせます
+  /// 最高記録は大きい方を残します
+  /// * `other` - 足す記録
+  pub fn merge(&mut self, other: &Stats) {
+    self.games_played += other.games_played;
+    self.robots_destroyed += other.robots_destroyed;
+    self.super_robots_destroyed += other.super_robots_destroyed;
+    self.teleports += other.teleports;
+    self.deaths_by_robot += other.deaths_by_robot;
+    self.deaths_by_scrap += other.deaths_by_scrap;
+    self.highest_level = std::cmp::max(self.highest_level, other.highest_level);
+    self.longest_stop_streak = std::cmp::max(self.longest_stop_streak, other.longest_stop_streak);
+  }
+
+  /// 表示用に(項目, 値)のリストを返します
+  pub fn rows(&self) -> Vec<(Stat, u64)> {
+    vec![
+      (Stat::GamesPlayed, self.games_played),
+      (Stat::RobotsDestroyed, self.robots_destroyed),
+      (Stat::SuperRobotsDestroyed, self.super_robots_destroyed),
+      (Stat::Teleports, self.teleports),
+      (Stat::DeathsByRobot, self.deaths_by_robot),
+      (Stat::DeathsByScrap, self.deaths_by_scrap),
+      (Stat::HighestLevel, self.highest_level as u64),
+      (Stat::LongestStopStreak, self.longest_stop_streak),
+    ]
+  }
+
+  /// 記録を`key value`の行にします
+  pub fn encode(&self) -> String {
+    format!("games_played {}\nrobots_destroyed {}\nsuper_robots_destroyed {}\nteleports {}\n\
+      deaths_by_robot {}\ndeaths_by_scrap {}\nhighest_level {}\nlongest_stop_streak {}\n",
+      self.games_played, self.robots_destroyed, self.super_robots_destroyed, self.teleports,
+      self.deaths_by_robot, self.deaths_by_scrap, self.highest_level, self.longest_stop_streak)
+  }
+
+  /// `encode`で作った文字列から記録を読み込みます
+  /// 読み込めない行は無視します
+  /// * `s` - 記録を表す文字列
+  pub fn decode(s: &str) -> Stats {
+    let mut stats = Stats::default();
+    for line in s.lines() {
+      let (key, value) = match line.split_once(' ') {
+        Some((key, value)) => (key, value.trim()),
+        None => continue,
+      };
+      let value: u64 = match value.parse() {
+        Ok(value) => value,
+        Err(_) => continue,
+      };
+      match key {
+        "games_played" => { stats.games_played = value; },
+        "robots_destroyed" => { stats.robots_destroyed = value; },
+        "super_robots_destroyed" => { stats.super_robots_destroyed = value; },
+        "teleports" => { stats.teleports = value; },
+        "deaths_by_robot" => { stats.deaths_by_robot = value; },
+        "deaths_by_scrap" => { stats.deaths_by_scrap = value; },
+        "highest_level" => { stats.highest_level = value as u32; },
+        "longest_stop_streak" => { stats.longest_stop_streak = value; },
+        _ => (),
+      }
+    }
+    stats
+  }
+}
+
+/// 記録ファイルから累計の記録を読み込みます
+/// ファイルが無い場合は空の記録を返します
+/// * `filename` - 記録ファイル名
+pub fn load(filename: &str) -> Stats {
+  match fs::read_to_string(filename) {
+    Ok(s) => Stats::decode(&s),
+    Err(_) => Stats::default(),
+  }
+}
+
+/// 今回のゲームの記録を記録ファイルの累計に足します
+/// * `filename` - 記録ファイル名
+/// * `session` - 今回のゲームの記録
+pub fn record(filename: &str, session: &Stats) -> std::io::Result<()> {
+  let mut stats = load(filename);
+  stats.merge(session);
+  fs::write(filename, stats.encode())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn record_turn_test() {
+    let mut stats = Stats::default();
+    stats.record_turn(&TurnReport { robots: 3, super_robots: 1, killed_by: None });
+    stats.record_turn(&TurnReport { robots: 0, super_robots: 0, killed_by: Some(Object::SuperRobot) });
+    stats.record_turn(&TurnReport { robots: 1, super_robots: 0, killed_by: Some(Object::Scrap) });
+    assert_eq!((stats.robots_destroyed, stats.super_robots_destroyed), (4, 1));
+    assert_eq!((stats.deaths_by_robot, stats.deaths_by_scrap), (1, 1));
+  }
+
+#[test]
+  fn merge_test() {
+    let mut total = Stats { games_played: 3, highest_level: 5, longest_stop_streak: 2, ..Stats::default() };
+    total.merge(&Stats { games_played: 1, teleports: 4, highest_level: 3, longest_stop_streak: 7, ..Stats::default() });
+    assert_eq!(total.games_played, 4);
+    assert_eq!(total.teleports, 4);
+    assert_eq!(total.highest_level, 5);
+    assert_eq!(total.longest_stop_streak, 7);
+  }
+
+#[test]
+  fn encode_decode_test() {
+    let stats = Stats {
+      games_played: 1, robots_destroyed: 2, super_robots_destroyed: 3, teleports: 4,
+      deaths_by_robot: 5, deaths_by_scrap: 6, highest_level: 7, longest_stop_streak: 8,
+    };
+    assert_eq!(Stats::decode(&stats.encode()), stats);
+    assert_eq!(Stats::decode("games_played x\nteleports 3\nunknown 1"), Stats { teleports: 3, ..Stats::default() });
+  }
+
+#[test]
+  fn record_file_test() {
+    let path = std::env::temp_dir().join(format!("robots-stats-{}.dat", std::process::id()));
+    let filename = path.to_str().unwrap();
+    let _ = fs::remove_file(filename);
+
+    let session = Stats { games_played: 1, robots_destroyed: 10, highest_level: 2, ..Stats::default() };
+    record(filename, &session).unwrap();
+    record(filename, &session).unwrap();
+    let stats = load(filename);
+    assert_eq!((stats.games_played, stats.robots_destroyed, stats.highest_level), (2, 20, 2));
+    fs::remove_file(filename).unwrap();
+  }
+}
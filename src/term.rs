@@ -0,0 +1,62 @@
+//! 画面の描画とキー入力を行う端末のバックエンドを抽象化します
+
+#[cfg(feature = "ncurses")]
+mod curses;
+mod ansi;
+
+pub use ansi::AnsiTerminal;
+#[cfg(feature = "ncurses")]
+pub use curses::Curses;
+
+/// 入力が無かったことを表すキー
+pub const ERR: i32 = -1;
+
+/// 画面への描画を表します
+/// 座標は画面の左上を(0, 0)とします
+pub trait Renderer {
+  /// 画面の大きさを(横幅, 縦幅)で返します
+  fn size(&self) -> (i32, i32);
+  /// カーソルを移動します
+  /// * `y` - 行
+  /// * `x` - 列
+  fn mv(&mut self, y: i32, x: i32);
+  /// カーソルの位置に文字列を描画します
+  /// * `s` - 描画する文字列
+  fn addstr(&mut self, s: &str);
+  /// カーソルの位置から行末までを消去します
+  fn clrtoeol(&mut self);
+  /// 画面全体を消去します
+  fn clear(&mut self);
+  /// 描画した内容を画面に反映します
+  fn refresh(&mut self);
+}
+
+/// キー入力を表します
+pub trait InputSource {
+  /// キー入力を1つ読み込みます
+  /// 待ち時間内に入力が無かった場合は`ERR`を返します
+  fn getch(&mut self) -> i32;
+  /// `getch`が入力を待つ時間を設定します
+  /// * `ms` - 待つ時間(ミリ秒)、負の値の場合は入力があるまで待ち続けます
+  fn timeout(&mut self, ms: i32);
+}
+
+/// 描画とキー入力の両方を行う端末を表します
+/// 破棄するときに端末の設定を元に戻します
+pub trait Terminal: Renderer + InputSource {}
+
+impl<T: Renderer + InputSource> Terminal for T {}
+
+/// 端末を初期化し、使用するバックエンドを返します
+/// ncursesが有効な場合は`ansi`が指定されない限りncursesを使います
+/// * `ansi` - ANSIエスケープシーケンスのバックエンドを使うか
+pub fn open(ansi: bool) -> std::io::Result<Box<dyn Terminal>> {
+  #[cfg(feature = "ncurses")]
+  {
+    if !ansi {
+      return Ok(Box::new(Curses::new()));
+    }
+  }
+  let _ = ansi;
+  Ok(Box::new(AnsiTerminal::new()?))
+}
@@ -0,0 +1,205 @@
+use std::io::{self, BufWriter, Stdout, Write};
+use super::{InputSource, Renderer, ERR};
+use crate::internal::KEY_QUIT;
+
+/// 端末の大きさが分からない場合に使う大きさ
+const DEFAULT_SIZE: (i32, i32) = (80, 24);
+/// Ctrl-Cで入力される文字
+const CTRL_C: u8 = 0x03;
+
+/// ANSIエスケープシーケンスで描画する出力先を表します
+pub struct AnsiRenderer<W: Write> {
+  /// 出力先
+  out: W,
+  /// 画面の大きさ(横幅, 縦幅)
+  size: (i32, i32),
+}
+
+impl<W: Write> AnsiRenderer<W> {
+  /// 出力先と画面の大きさを指定して生成します
+  /// * `out` - 出力先
+  /// * `size` - 画面の大きさ(横幅, 縦幅)
+  pub fn new(out: W, size: (i32, i32)) -> AnsiRenderer<W> {
+    AnsiRenderer { out, size }
+  }
+
+  /// エスケープシーケンスを出力します
+  /// 出力できない場合は描画を諦めます
+  /// * `s` - 出力する文字列
+  fn write(&mut self, s: &str) {
+    let _ = self.out.write_all(s.as_bytes());
+  }
+}
+
+impl<W: Write> Renderer for AnsiRenderer<W> {
+  fn size(&self) -> (i32, i32) {
+    self.size
+  }
+
+  fn mv(&mut self, y: i32, x: i32) {
+    self.write(&format!("\x1b[{};{}H", y + 1, x + 1));
+  }
+
+  fn addstr(&mut self, s: &str) {
+    self.write(s);
+  }
+
+  fn clrtoeol(&mut self) {
+    self.write("\x1b[K");
+  }
+
+  fn clear(&mut self) {
+    self.write("\x1b[2J\x1b[H");
+  }
+
+  fn refresh(&mut self) {
+    let _ = self.out.flush();
+  }
+}
+
+/// Cライブラリを使わずにANSIエスケープシーケンスと端末のrawモードで動く端末を表します
+pub struct AnsiTerminal {
+  /// 標準出力への描画
+  renderer: AnsiRenderer<BufWriter<Stdout>>,
+  /// 元に戻す端末の設定
+  original: libc::termios,
+  /// `getch`が入力を待つ時間(ミリ秒)
+  timeout: i32,
+}
+
+impl AnsiTerminal {
+  /// 端末をrawモードにして代替画面に切り替えます
+  /// 標準入力が端末でない場合はエラーを返します
+  pub fn new() -> io::Result<AnsiTerminal> {
+    // SAFETY: termiosは整数の配列だけを持つ構造体で、0埋めで初期化できる
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    // SAFETY: 有効なファイルディスクリプタと書き込み可能なtermiosを渡している
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+      return Err(io::Error::last_os_error());
+    }
+    // 1文字ずつ読み込み、入力した文字を表示しない
+    // Ctrl-Cでプロセスが終了すると端末の設定を戻せないので、シグナルにせず文字として読み込む
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+    raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+    raw.c_cc[libc::VMIN] = 1;
+    raw.c_cc[libc::VTIME] = 0;
+    // SAFETY: tcgetattrで取得した設定を変更して渡している
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+      return Err(io::Error::last_os_error());
+    }
+
+    let mut term = AnsiTerminal {
+      renderer: AnsiRenderer::new(BufWriter::new(io::stdout()), DEFAULT_SIZE),
+      original,
+      timeout: -1,
+    };
+    // 代替画面に切り替えてカーソルを隠す
+    term.renderer.write("\x1b[?1049h\x1b[?25l");
+    term.renderer.clear();
+    term.renderer.refresh();
+    Ok(term)
+  }
+}
+
+impl Renderer for AnsiTerminal {
+  fn size(&self) -> (i32, i32) {
+    // SAFETY: winsizeは整数だけを持つ構造体で、0埋めで初期化できる
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    // SAFETY: TIOCGWINSZには書き込み可能なwinsizeを渡す
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } == 0;
+    if ok && ws.ws_col > 0 && ws.ws_row > 0 {
+      (ws.ws_col as i32, ws.ws_row as i32)
+    } else {
+      DEFAULT_SIZE
+    }
+  }
+
+  fn mv(&mut self, y: i32, x: i32) {
+    self.renderer.mv(y, x);
+  }
+
+  fn addstr(&mut self, s: &str) {
+    self.renderer.addstr(s);
+  }
+
+  fn clrtoeol(&mut self) {
+    self.renderer.clrtoeol();
+  }
+
+  fn clear(&mut self) {
+    self.renderer.clear();
+  }
+
+  fn refresh(&mut self) {
+    self.renderer.refresh();
+  }
+}
+
+impl InputSource for AnsiTerminal {
+  fn getch(&mut self) -> i32 {
+    // ncursesと同様に入力を待つ前に描画を反映する
+    self.renderer.refresh();
+    let mut fds = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    // SAFETY: 1つのpollfdを指すポインタと要素数1を渡している
+    if unsafe { libc::poll(&mut fds, 1, self.timeout) } <= 0 {
+      return ERR;
+    }
+    let mut buf = [0u8; 1];
+    // SAFETY: 1バイトのバッファに1バイトまで読み込む
+    match unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr() as *mut libc::c_void, 1) } {
+      1 => key_code(buf[0]),
+      _ => ERR,
+    }
+  }
+
+  fn timeout(&mut self, ms: i32) {
+    self.timeout = ms;
+  }
+}
+
+/// 読み込んだ文字をキーの値にします
+/// Ctrl-Cは終了のキーとして扱います
+/// * `byte` - 読み込んだ文字
+fn key_code(byte: u8) -> i32 {
+  match byte {
+    CTRL_C => KEY_QUIT,
+    byte => byte as i32,
+  }
+}
+
+impl Drop for AnsiTerminal {
+  fn drop(&mut self) {
+    // カーソルを表示して元の画面に戻す
+    self.renderer.write("\x1b[?25h\x1b[?1049l");
+    self.renderer.refresh();
+    // SAFETY: 初期化時に取得した設定を渡している
+    unsafe {
+      libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn ansi_renderer_test() {
+    let mut r = AnsiRenderer::new(Vec::new(), (40, 10));
+    assert_eq!(r.size(), (40, 10));
+    r.clear();
+    r.mv(1, 0);
+    r.addstr("you win");
+    r.clrtoeol();
+    r.mv(4, 9);
+    r.addstr("@");
+    assert_eq!(String::from_utf8(r.out).unwrap(), "\x1b[2J\x1b[H\x1b[2;1Hyou win\x1b[K\x1b[5;10H@");
+  }
+
+#[test]
+  fn key_code_test() {
+    assert_eq!(key_code(CTRL_C), KEY_QUIT);
+    assert_eq!(key_code(b'h'), b'h' as i32);
+  }
+}
@@ -0,0 +1,64 @@
+use ncurses::*;
+use super::{InputSource, Renderer};
+
+/// ncursesを使う端末を表します
+pub struct Curses;
+
+impl Curses {
+  /// ncursesを初期化します
+  pub fn new() -> Curses {
+    // UTF-8の記号を描画できるように環境変数のロケールを使う
+    setlocale(LcCategory::all, "");
+    initscr();
+    noecho();
+    nonl();
+    intrflush(stdscr(), true);
+    keypad(stdscr(), true);
+    Curses
+  }
+}
+
+impl Renderer for Curses {
+  fn size(&self) -> (i32, i32) {
+    let mut w: i32 = 0;
+    let mut h: i32 = 0;
+    getmaxyx(stdscr(), &mut h, &mut w);
+    (w, h)
+  }
+
+  fn mv(&mut self, y: i32, x: i32) {
+    mv(y, x);
+  }
+
+  fn addstr(&mut self, s: &str) {
+    addstr(s);
+  }
+
+  fn clrtoeol(&mut self) {
+    clrtoeol();
+  }
+
+  fn clear(&mut self) {
+    clear();
+  }
+
+  fn refresh(&mut self) {
+    refresh();
+  }
+}
+
+impl InputSource for Curses {
+  fn getch(&mut self) -> i32 {
+    getch()
+  }
+
+  fn timeout(&mut self, ms: i32) {
+    timeout(ms);
+  }
+}
+
+impl Drop for Curses {
+  fn drop(&mut self) {
+    endwin();
+  }
+}
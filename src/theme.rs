@@ -0,0 +1,180 @@
+use crate::internal::Object;
+
+/// fieldの描画に使う記号を表します
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+  /// player
+  pub player: char,
+  /// 協力プレイでの2人目のplayer
+  pub partner: char,
+  /// robot
+  pub robot: char,
+  /// super_robot
+  pub super_robot: char,
+  /// smart_robot
+  pub smart_robot: char,
+  /// scrap
+  pub scrap: char,
+  /// 何もないマス
+  pub empty: char,
+  /// フレームの横線
+  pub horizontal: char,
+  /// フレームの縦線
+  pub vertical: char,
+  /// フレームの角(左上, 右上, 左下, 右下)
+  pub corners: [char; 4],
+  /// 表示範囲外のrobotを示す矢印(上, 下, 左, 右)
+  pub arrows: [char; 4],
+  /// 1マスを2列で描画して縦横の距離の見た目を揃えるか
+  pub square: bool,
+}
+
+impl Theme {
+  /// ASCII文字だけを使うテーマを返します
+  pub fn ascii() -> Theme {
+    Theme {
+      player: '@',
+      partner: '&',
+      robot: '+',
+      super_robot: '$',
+      smart_robot: '%',
+      scrap: '*',
+      empty: ' ',
+      horizontal: '-',
+      vertical: '|',
+      corners: [' '; 4],
+      arrows: ['^', 'v', '<', '>'],
+      square: false,
+    }
+  }
+
+  /// 罫線や記号にUnicodeの文字を使うテーマを返します
+  pub fn unicode() -> Theme {
+    Theme {
+      player: '☺',
+      partner: '☻',
+      robot: '●',
+      super_robot: '◆',
+      smart_robot: '◉',
+      scrap: '▒',
+      empty: ' ',
+      horizontal: '─',
+      vertical: '│',
+      corners: ['┌', '┐', '└', '┘'],
+      arrows: ['▲', '▼', '◀', '▶'],
+      square: false,
+    }
+  }
+
+  /// CJKのロケールの端末向けに、Unicodeの記号を使うテーマを返します
+  /// East Asian Ambiguousの文字はCJKの端末では2列で表示されるので、
+  /// East Asian Widthが中立か半角の文字だけを使います
+  pub fn unicode_cjk() -> Theme {
+    Theme {
+      player: '☺',
+      partner: '☻',
+      robot: '◍',
+      super_robot: '♦',
+      smart_robot: '◉',
+      scrap: '░',
+      empty: ' ',
+      horizontal: '⎯',
+      vertical: '⏐',
+      corners: ['⌜', '⌝', '⌞', '⌟'],
+      arrows: ['￪', '￬', '￩', '￫'],
+      square: false,
+    }
+  }
+
+  /// 端末のロケールに合わせてUnicodeのテーマを返します
+  /// Unicodeのテーマの罫線や記号はEast Asian Ambiguousの文字で、CJKのロケールの端末では2列で表示されて
+  /// fieldの列がずれるので、その場合は`unicode_cjk`のテーマを返します
+  pub fn unicode_for_locale() -> Theme {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+      .filter_map(|name| std::env::var(name).ok())
+      .find(|value| !value.is_empty())
+      .unwrap_or_default();
+    if is_cjk_locale(&locale) { Theme::unicode_cjk() } else { Theme::unicode() }
+  }
+
+  /// Unicodeの記号を使うテーマかを返します
+  pub fn is_unicode(&self) -> bool {
+    self.player != Theme::ascii().player
+  }
+
+  /// objectを表す記号を返します
+  /// * `obj` - 描画するobject
+  pub fn glyph(&self, obj: Object) -> char {
+    match obj {
+      Object::Player => self.player,
+      Object::Partner => self.partner,
+      Object::Robot => self.robot,
+      Object::SuperRobot => self.super_robot,
+      Object::SmartRobot => self.smart_robot,
+      Object::Scrap => self.scrap,
+      Object::Null => self.empty,
+    }
+  }
+
+  /// 1マスを描画する列数を返します
+  pub fn cell_width(&self) -> usize {
+    if self.square { 2 } else { 1 }
+  }
+
+  /// 1マス分の文字列を返します
+  /// 2列で描画する場合、scrapは2列とも埋めてそれ以外は右側を空けます
+  /// * `obj` - 描画するobject
+  pub fn cell(&self, obj: Object) -> String {
+    let glyph = self.glyph(obj);
+    match (self.square, obj) {
+      (false, _) => glyph.to_string(),
+      (true, Object::Scrap) => format!("{}{}", glyph, glyph),
+      (true, _) => format!("{} ", glyph),
+    }
+  }
+
+  /// `Viewport::edge_indicator`が返す矢印をテーマの矢印に変換します
+  /// * `arrow` - `^`, `v`, `<`, `>`のいずれか
+  pub fn arrow(&self, arrow: char) -> char {
+    match arrow {
+      '^' => self.arrows[0],
+      'v' => self.arrows[1],
+      '<' => self.arrows[2],
+      _ => self.arrows[3],
+    }
+  }
+}
+
+/// East Asian Ambiguousの文字を2列で表示するCJKのロケールかを返します
+/// * `locale` - `ja_JP.UTF-8`のようなロケール名
+fn is_cjk_locale(locale: &str) -> bool {
+  ["ja", "zh", "ko"].iter().any(|lang| locale.starts_with(lang))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn cell_test() {
+    let mut theme = Theme::ascii();
+    assert_eq!(theme.cell(Object::Robot), "+");
+    assert_eq!(theme.cell_width(), 1);
+    theme.square = true;
+    assert_eq!(theme.cell(Object::Robot), "+ ");
+    assert_eq!(theme.cell(Object::Scrap), "**");
+    assert_eq!(theme.cell_width(), 2);
+    assert_eq!(Theme::unicode().arrow('v'), '▼');
+    assert!(Theme::unicode().is_unicode());
+    assert!(Theme::unicode_cjk().is_unicode());
+    assert!(!Theme::ascii().is_unicode());
+  }
+
+#[test]
+  fn cjk_locale_test() {
+    assert!(is_cjk_locale("ja_JP.UTF-8"));
+    assert!(is_cjk_locale("zh_TW.UTF-8"));
+    assert!(!is_cjk_locale("en_US.UTF-8"));
+    assert!(!is_cjk_locale("C.UTF-8"));
+  }
+}
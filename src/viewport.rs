@@ -0,0 +1,243 @@
+use crate::internal::*;
+use crate::term::Renderer;
+use crate::theme::Theme;
+
+/// ミニマップの最大の横幅
+const MINIMAP_MAX_WIDTH: usize = 24;
+
+/// fieldのうち画面に表示する範囲を表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Viewport {
+  /// 表示範囲の左上のfield上の座標
+  pub offset: Point,
+  /// 表示する横幅
+  pub width: usize,
+  /// 表示する縦幅
+  pub height: usize,
+}
+
+impl Viewport {
+  /// 表示範囲を生成します
+  /// 表示範囲がfieldより大きい場合はfieldの大きさに合わせます
+  /// * `width` - 表示できる最大の横幅
+  /// * `height` - 表示できる最大の縦幅
+  /// * `field` - 表示するfield
+  pub fn new(width: usize, height: usize, field: &Field) -> Viewport {
+    let mut view = Viewport {
+      offset: Point::new(0, 0),
+      width: std::cmp::max(std::cmp::min(width, field.width), 1),
+      height: std::cmp::max(std::cmp::min(height, field.height), 1),
+    };
+    view.follow(field.player_pos, field.width, field.height);
+    view
+  }
+
+  /// `target`が中央に来るように表示範囲を移動します
+  /// 表示範囲はfieldの外にはみ出しません
+  /// * `target` - 追いかける座標
+  /// * `field_width` - fieldの横幅
+  /// * `field_height` - fieldの縦幅
+  pub fn follow(&mut self, target: Point, field_width: usize, field_height: usize) {
+    let max_x = field_width.saturating_sub(self.width);
+    let max_y = field_height.saturating_sub(self.height);
+    self.offset.x = std::cmp::min(target.x.saturating_sub(self.width / 2), max_x);
+    self.offset.y = std::cmp::min(target.y.saturating_sub(self.height / 2), max_y);
+  }
+
+  /// 指定の座標が表示範囲に含まれるかを判定します
+  /// * `p` - 判定する座標
+  pub fn contains(&self, p: Point) -> bool {
+    p.x >= self.offset.x && p.x < self.offset.x + self.width &&
+    p.y >= self.offset.y && p.y < self.offset.y + self.height
+  }
+
+  /// 表示範囲外の座標を示すフレーム上の位置と記号を返します
+  /// 表示範囲内の座標の場合は`None`を返します
+  /// 戻り値の座標は表示範囲の左上を(1, 1)とした座標です
+  /// * `p` - 表示範囲外の座標
+  pub fn edge_indicator(&self, p: Point) -> Option<(Point, char)> {
+    if self.contains(p) {
+      return None;
+    }
+    let right = self.offset.x + self.width - 1;
+    let bottom = self.offset.y + self.height - 1;
+    let x = std::cmp::min(std::cmp::max(p.x, self.offset.x), right) - self.offset.x + 1;
+    let y = std::cmp::min(std::cmp::max(p.y, self.offset.y), bottom) - self.offset.y + 1;
+
+    if p.y < self.offset.y {
+      Some((Point::new(x, 0), '^'))
+    } else if p.y > bottom {
+      Some((Point::new(x, self.height + 1), 'v'))
+    } else if p.x < self.offset.x {
+      Some((Point::new(0, y), '<'))
+    } else {
+      Some((Point::new(self.width + 1, y), '>'))
+    }
+  }
+}
+
+/// fieldの全体を縮小して表示するミニマップを表します
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Minimap {
+  /// 画面上の表示位置
+  pub pos: Point,
+  /// 横幅
+  pub width: usize,
+  /// 縦幅
+  pub height: usize,
+}
+
+impl Minimap {
+  /// field上の座標をミニマップ上の座標に変換します
+  /// * `pos` - field上の座標
+  /// * `size` - ミニマップの大きさ
+  /// * `field_size` - fieldの大きさ
+  fn cell_of(pos: usize, size: usize, field_size: usize) -> usize {
+    std::cmp::min(pos * size / field_size, size - 1)
+  }
+
+  /// ミニマップを描画します
+  /// 表示範囲内は`.`で、範囲外は空白で埋めます
+  /// * `out` - 描画先
+  /// * `field` - 描画するfield
+  /// * `view` - 現在の表示範囲
+  /// * `theme` - 描画に使う記号
+  pub fn print(&self, out: &mut dyn Renderer, field: &Field, view: &Viewport, theme: &Theme) {
+    // 優先度の高いobjectほど後から書き込む
+    let mut cells = vec![vec![' '; self.width]; self.height];
+    for (y, row) in cells.iter_mut().enumerate() {
+      for (x, cell) in row.iter_mut().enumerate() {
+        let p = Point::new(x * field.width / self.width, y * field.height / self.height);
+        if view.contains(p) {
+          *cell = '.';
+        }
+      }
+    }
+    let layers: [(Vec<Point>, char); 6] = [
+      (field.scraps_pos.iter().copied().collect(), theme.scrap),
+      (field.robots_pos.clone(), theme.robot),
+      (field.smart_robots_pos.clone(), theme.smart_robot),
+      (field.super_robots_pos.clone(), theme.super_robot),
+      (field.partner_pos.filter(|_| field.partner_alive).into_iter().collect(), theme.partner),
+      (field.player(0).into_iter().collect(), theme.player),
+    ];
+    for (points, glyph) in layers.iter() {
+      for p in points {
+        let x = Minimap::cell_of(p.x, self.width, field.width);
+        let y = Minimap::cell_of(p.y, self.height, field.height);
+        cells[y][x] = *glyph;
+      }
+    }
+
+    let x = self.pos.x as i32;
+    let y = self.pos.y as i32;
+    let frame: String = std::iter::repeat_n(theme.horizontal, self.width).collect();
+    out.mv(y-1, x-1);
+    out.addstr(&format!("{}{}{}", theme.corners[0], frame, theme.corners[1]));
+    out.mv(y+self.height as i32, x-1);
+    out.addstr(&format!("{}{}{}", theme.corners[2], frame, theme.corners[3]));
+    for (row, line) in cells.iter().enumerate() {
+      out.mv(y + row as i32, x-1);
+      out.addstr(&format!("{}{}{}", theme.vertical, line.iter().collect::<String>(), theme.vertical));
+    }
+  }
+}
+
+/// 画面の大きさからfieldの表示範囲とミニマップの配置を決めます
+/// fieldが画面に収まる場合はミニマップを表示しません
+/// * `field` - 表示するfield
+/// * `scr_w` - 画面の横幅
+/// * `scr_h` - 画面の縦幅
+/// * `cell_width` - fieldの1マスを描画する列数
+pub fn layout(field: &Field, scr_w: i32, scr_h: i32, cell_width: usize) -> (Viewport, Option<Minimap>) {
+  let avail_w = std::cmp::max((scr_w - field.pos.x as i32 - 3) / cell_width as i32, 1) as usize;
+  let avail_h = std::cmp::max(scr_h - field.pos.y as i32 - 1, 1) as usize;
+  if field.width <= avail_w && field.height <= avail_h {
+    return (Viewport::new(avail_w, avail_h, field), None);
+  }
+
+  // 右側にミニマップを配置する
+  let mm_w = std::cmp::min(std::cmp::min(MINIMAP_MAX_WIDTH, field.width), avail_w / 3);
+  if mm_w < 2 {
+    return (Viewport::new(avail_w, avail_h, field), None);
+  }
+  // 端末の文字は縦長なので縦方向を半分に縮める
+  let mm_h = std::cmp::max(std::cmp::min(field.height * mm_w / field.width / 2, avail_h), 1);
+  let mm_h = std::cmp::min(mm_h, field.height);
+  // ミニマップは1マス1列で描画する
+  let mm_cells = (mm_w + 3).div_ceil(cell_width);
+  let view = Viewport::new(avail_w.saturating_sub(mm_cells), avail_h, field);
+  let minimap = Minimap {
+    pos: Point::new(field.pos.x + view.width * cell_width + 3, field.pos.y),
+    width: mm_w,
+    height: mm_h,
+  };
+  (view, Some(minimap))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+#[test]
+  fn viewport_follow_test() {
+    let field = Field::new(Point::new(0, 0), 100, 50, 0);
+    let mut view = Viewport::new(20, 10, &field);
+    assert_eq!(view.offset, Point::new(40, 20));
+
+    view.follow(Point::new(0, 0), field.width, field.height);
+    assert_eq!(view.offset, Point::new(0, 0));
+
+    view.follow(Point::new(99, 49), field.width, field.height);
+    assert_eq!(view.offset, Point::new(80, 40));
+    assert!(view.contains(Point::new(99, 49)));
+    assert!(!view.contains(Point::new(79, 49)));
+  }
+
+#[test]
+  fn viewport_small_field_test() {
+    let field = Field::new(Point::new(0, 0), 10, 5, 0);
+    let view = Viewport::new(20, 10, &field);
+    assert_eq!(view.width, 10);
+    assert_eq!(view.height, 5);
+    assert_eq!(view.offset, Point::new(0, 0));
+  }
+
+#[test]
+  fn edge_indicator_test() {
+    let view = Viewport { offset: Point::new(10, 10), width: 5, height: 5 };
+    assert_eq!(view.edge_indicator(Point::new(12, 12)), None);
+    assert_eq!(view.edge_indicator(Point::new(12, 0)), Some((Point::new(3, 0), '^')));
+    assert_eq!(view.edge_indicator(Point::new(0, 20)), Some((Point::new(1, 6), 'v')));
+    assert_eq!(view.edge_indicator(Point::new(0, 11)), Some((Point::new(0, 2), '<')));
+    assert_eq!(view.edge_indicator(Point::new(30, 14)), Some((Point::new(6, 5), '>')));
+  }
+
+#[test]
+  fn layout_test() {
+    let field = Field::new(Point::new(5, 5), 50, 20, 0);
+    let (view, minimap) = layout(&field, 80, 30, 1);
+    assert_eq!((view.width, view.height), (50, 20));
+    assert_eq!(minimap, None);
+
+    let field = Field::new(Point::new(5, 5), 200, 100, 0);
+    let (view, minimap) = layout(&field, 80, 30, 1);
+    let minimap = minimap.unwrap();
+    assert!(view.width < 200 && view.height < 100);
+    assert!(minimap.pos.x + minimap.width < 80);
+  }
+
+#[test]
+  fn square_layout_test() {
+    let field = Field::new(Point::new(5, 5), 30, 20, 0);
+    let (view, minimap) = layout(&field, 80, 30, 2);
+    assert_eq!((view.width, view.height), (30, 20));
+    assert_eq!(minimap, None);
+
+    let field = Field::new(Point::new(5, 5), 50, 20, 0);
+    let (view, minimap) = layout(&field, 80, 30, 2);
+    let minimap = minimap.unwrap();
+    assert!(field.pos.x + view.width * 2 + 1 < minimap.pos.x);
+    assert!(minimap.pos.x + minimap.width < 80);
+  }
+}